@@ -0,0 +1,277 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{ToTokens, quote};
+use syn::{
+    DeriveInput, Expr, ExprLit, ExprPath, Ident, Lit, LitInt, Token, Type, parse::ParseStream,
+    parse_macro_input, parse_quote,
+};
+
+/**
+ * Registers a component as a gene.  Each #[gene(...)] attribute on the type registers one gene,
+ * generic components are registered once per instantiation by naming it first:
+ *
+ * ```ignore
+ * #[derive(Component, Gene)]
+ * #[gene(id = "activation", version = 1, salt = 0)]
+ * pub struct Activation { ... }
+ *
+ * #[derive(Component, Gene)]
+ * #[gene(Accumulator<Dopamine>, id = "accumulator.dopamine", version = 1, salt = 0)]
+ * #[gene(Accumulator<Serotonin>, id = "accumulator.serotonin", version = 1, salt = 2)]
+ * pub struct Accumulator<T> { ... }
+ * ```
+ *
+ * The salt pins the candidate header the gene is read with, so registering other genes never
+ * moves it.  Leaving it out lets the gene take the first candidate that fits, which is how a new
+ * gene finds the salt to pin.
+ * The id can be any constant &'static str expression, e.g. concat!("accumulator.", "dopamine"),
+ * so macros can build it.  The instantiation doesn't have to be the type the derive sits on.
+ * The parser defaults to the type's sequence_parser and can be swapped with parser = path, the
+ * encoder and the text hooks are always the type's GeneEncoder implementation.  The generated
+ * code registers with crate::component_register and crate::config, so the derive is only meant to
+ * be used inside the simulation crate.
+ */
+#[proc_macro_derive(Gene, attributes(gene))]
+pub fn derive_gene(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+// Everything a single #[gene(...)] attribute says
+struct GeneAttribute {
+    ty: Type,
+    id: Expr,
+    version: LitInt,
+    parser: ExprPath,
+    salt: Option<LitInt>,
+}
+
+fn parse_attribute(input: &DeriveInput, attr: &syn::Attribute) -> syn::Result<GeneAttribute> {
+    attr.parse_args_with(|stream: ParseStream| {
+        // A leading type names the instantiation, everything else is key = value
+        let ty: Option<Type> = if stream.peek(Ident) && stream.peek2(Token![=]) {
+            None
+        } else {
+            let ty = stream.parse()?;
+            if !stream.is_empty() {
+                stream.parse::<Token![,]>()?;
+            }
+            Some(ty)
+        };
+
+        let mut id: Option<Expr> = None;
+        let mut version: Option<LitInt> = None;
+        let mut parser: Option<ExprPath> = None;
+        let mut salt: Option<LitInt> = None;
+        while !stream.is_empty() {
+            let key: Ident = stream.parse()?;
+            stream.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "id" => id = Some(stream.parse()?),
+                "version" => version = Some(stream.parse()?),
+                "parser" => parser = Some(stream.parse()?),
+                "salt" => salt = Some(stream.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected id, version, parser or salt",
+                    ));
+                }
+            }
+            if !stream.is_empty() {
+                stream.parse::<Token![,]>()?;
+            }
+        }
+
+        let ty = match ty {
+            Some(ty) => ty,
+            None if input.generics.params.is_empty() => {
+                let name = &input.ident;
+                parse_quote!(#name)
+            }
+            None => {
+                return Err(syn::Error::new(
+                    stream.span(),
+                    "generic genes must name the instantiation, e.g. #[gene(Accumulator<Dopamine>, ...)]",
+                ));
+            }
+        };
+        let id = id.ok_or_else(|| syn::Error::new(stream.span(), "missing id = \"...\""))?;
+        if let Expr::Lit(ExprLit {
+            lit: Lit::Str(literal),
+            ..
+        }) = &id
+            && literal.value().is_empty()
+        {
+            return Err(syn::Error::new(literal.span(), "gene ids can't be empty"));
+        }
+        let version =
+            version.ok_or_else(|| syn::Error::new(stream.span(), "missing version = ..."))?;
+        version.base10_parse::<u32>()?;
+        if let Some(salt) = &salt {
+            salt.base10_parse::<u32>()?;
+        }
+        let parser = parser.unwrap_or_else(|| parse_quote!(<#ty>::sequence_parser));
+
+        Ok(GeneAttribute {
+            ty,
+            id,
+            version,
+            parser,
+            salt,
+        })
+    })
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attributes = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("gene"))
+        .map(|attr| parse_attribute(input, attr))
+        .collect::<syn::Result<Vec<_>>>()?;
+    if attributes.is_empty() {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "Gene needs at least one #[gene(id = \"...\", version = ...)] attribute",
+        ));
+    }
+
+    let genes = attributes.iter().map(|attribute| {
+        let GeneAttribute {
+            ty,
+            id,
+            version,
+            parser,
+            salt,
+        } = attribute;
+        let salt = match salt {
+            Some(salt) => quote!(::core::option::Option::Some(#salt)),
+            None => quote!(::core::option::Option::None),
+        };
+        // Written the way the type appears in the source, which is what specs refer to it by
+        let type_str = ty
+            .to_token_stream()
+            .to_string()
+            .replace(' ', "")
+            .replace(',', ", ");
+        quote! {
+            impl ::gene_traits::header::Gene for #ty {
+                const ID: ::gene_traits::header::GeneId =
+                    ::gene_traits::header::GeneId::new(#id, #version);
+            }
+
+            ::inventory::submit! {
+                crate::component_register::ComponentRegister::<{ crate::config::PROMOTER_SIZE }> {
+                    id: <#ty as ::gene_traits::header::Gene>::ID,
+                    salt: #salt,
+                    type_str: #type_str,
+                    parser: #parser,
+                    encoder: crate::component_register::encode_component::<#ty>,
+                    from_text: <#ty as crate::component_register::GeneEncoder>::payload_from_text,
+                    to_text: <#ty as crate::component_register::GeneEncoder>::payload_to_text,
+                }
+            }
+        }
+    });
+    Ok(quote! { #(#genes)* })
+}
+
+#[cfg(test)]
+mod test {
+    use syn::{DeriveInput, parse_quote};
+
+    use super::expand;
+
+    fn expanded(input: DeriveInput) -> String {
+        expand(&input).unwrap().to_string()
+    }
+
+    fn error(input: DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn registers_plain_types() {
+        let code = expanded(parse_quote! {
+            #[gene(id = "activation", version = 1)]
+            struct Activation;
+        });
+
+        assert!(code.contains("impl :: gene_traits :: header :: Gene for Activation"));
+        assert!(code.contains("GeneId :: new (\"activation\" , 1)"));
+        assert!(code.contains("parser : < Activation > :: sequence_parser"));
+        assert!(code.contains("encode_component :: < Activation >"));
+        assert!(code.contains(
+            "from_text : < Activation as crate :: component_register :: GeneEncoder > :: payload_from_text"
+        ));
+        assert!(code.contains(
+            "to_text : < Activation as crate :: component_register :: GeneEncoder > :: payload_to_text"
+        ));
+    }
+
+    #[test]
+    fn registers_every_instantiation() {
+        let code = expanded(parse_quote! {
+            #[gene(Accumulator<Dopamine>, id = "accumulator.dopamine", version = 1, salt = 3)]
+            #[gene(Accumulator<Serotonin>, id = "accumulator.serotonin", version = 2, parser = serotonin_parser)]
+            struct Accumulator<T>(T);
+        });
+
+        assert_eq!(code.matches("inventory :: submit !").count(), 2);
+        assert!(code.contains("Gene for Accumulator < Dopamine >"));
+        assert!(code.contains("parser : < Accumulator < Dopamine > > :: sequence_parser"));
+        assert!(code.contains("type_str : \"Accumulator<Dopamine>\""));
+        assert!(code.contains("GeneId :: new (\"accumulator.serotonin\" , 2)"));
+        assert!(code.contains("parser : serotonin_parser"));
+        assert!(code.contains("salt : :: core :: option :: Option :: Some (3)"));
+        assert!(code.contains("salt : :: core :: option :: Option :: None"));
+    }
+
+    #[test]
+    fn ids_can_be_built_by_macros() {
+        let code = expanded(parse_quote! {
+            #[gene(Clearance<Dopamine>, id = concat!("clearance.", stringify!(dopamine)), version = 1)]
+            struct Dopamine;
+        });
+
+        assert!(code.contains("Gene for Clearance < Dopamine >"));
+        assert!(
+            code.contains("GeneId :: new (concat ! (\"clearance.\" , stringify ! (dopamine)) , 1)")
+        );
+        assert!(code.contains("type_str : \"Clearance<Dopamine>\""));
+    }
+
+    #[test]
+    fn rejects_incomplete_attributes() {
+        assert!(
+            error(parse_quote! {
+                struct Activation;
+            })
+            .contains("at least one")
+        );
+        assert!(
+            error(parse_quote! {
+                #[gene(version = 1)]
+                struct Activation;
+            })
+            .contains("missing id")
+        );
+        assert!(
+            error(parse_quote! {
+                #[gene(id = "accumulator", version = 1)]
+                struct Accumulator<T>(T);
+            })
+            .contains("must name the instantiation")
+        );
+        assert!(
+            error(parse_quote! {
+                #[gene(id = "activation", version = 1, colour = 3)]
+                struct Activation;
+            })
+            .contains("expected id, version, parser or salt")
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    amino_acid::AminoAcid,
+    dna::{hash_byte, header_from_hash},
+};
+
+/**
+ * The name a gene is registered under.  Headers are derived from it rather than from the Rust type
+ * that is built, so renaming or reshaping that type leaves saved genomes readable.  Bump the
+ * version when the payload a gene is written with changes meaning, the gene then gets a header of
+ * its own and old genomes don't get misread.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GeneId {
+    pub name: &'static str,
+    pub version: u32,
+}
+
+impl GeneId {
+    pub const fn new(name: &'static str, version: u32) -> Self {
+        Self { name, version }
+    }
+
+    // 128 bit FNV-1a over the name and the version, finished off with a remix so that every byte
+    // of the hash depends on the whole id
+    pub const fn hash(&self) -> u128 {
+        const OFFSET: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+        const PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+        let mut hash = OFFSET;
+        let name = self.name.as_bytes();
+        let mut i = 0;
+        while i < name.len() {
+            hash ^= name[i] as u128;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+        let version = self.version.to_le_bytes();
+        let mut i = 0;
+        while i < version.len() {
+            hash ^= version[i] as u128;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+        hash ^= hash >> 67;
+        hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+        hash ^ (hash >> 61)
+    }
+}
+
+// Implemented by #[derive(Gene)] for every type registered as a gene
+pub trait Gene {
+    const ID: GeneId;
+}
+
+pub use gene_derive::Gene;
+
+impl Display for GeneId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+// How many candidate headers are tried for a single gene before giving up
+pub const MAX_ATTEMPTS: u32 = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderAssignmentError {
+    // The same id was registered more than once
+    DuplicateId(GeneId),
+    // Two pinned headers are too close to be told apart
+    TooClose {
+        first: GeneId,
+        second: GeneId,
+        distance: usize,
+    },
+    // No candidate was far enough from the headers handed out before it
+    Exhausted {
+        id: GeneId,
+        min_distance: usize,
+    },
+}
+
+impl Display for HeaderAssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderAssignmentError::DuplicateId(id) => {
+                write!(f, "{} is registered more than once", id)
+            }
+            HeaderAssignmentError::TooClose {
+                first,
+                second,
+                distance,
+            } => write!(
+                f,
+                "the pinned headers of {} and {} are only {} edits apart",
+                first, second, distance
+            ),
+            HeaderAssignmentError::Exhausted { id, min_distance } => write!(
+                f,
+                "no header for {} is {} edits away from the others",
+                id, min_distance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeaderAssignmentError {}
+
+/**
+ * The headers tried for a gene, in order of their salt.  Salt 0 is the one get_header derives from
+ * the hash, the rest draw every amino acid evenly from a remixed hash.
+ */
+pub fn candidate_header<const N: usize>(hash: u128, salt: u32) -> [AminoAcid; N] {
+    if salt == 0 {
+        return header_from_hash(hash);
+    }
+    let seed = hash ^ (salt as u128).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+    std::array::from_fn(|i| {
+        // Starting at byte 16 makes sure the seed is mixed before it is used
+        let value = (hash_byte(seed, 16 + i) & 0xff) % 20;
+        AminoAcid::try_from(value as u8).expect("Below 20")
+    })
+}
+
+// A gene asking for a header.  A pinned salt is the candidate the gene has been read with so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderRequest {
+    pub id: GeneId,
+    pub salt: Option<u32>,
+}
+
+// The header handed out to a gene and the salt of the candidate it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssignedHeader<const N: usize> {
+    pub header: [AminoAcid; N],
+    pub salt: u32,
+}
+
+/**
+ * Hands every gene a header that is at least min_distance edits away from all the others, and
+ * returns them in the order of the requests given.
+ * Pinned genes always get the candidate of their salt, so registering new genes never moves them,
+ * and assigning fails when two of them are too close.  The others are served in ascending hash
+ * order, each taking its first candidate that fits, so the same set of genes always gets the same
+ * headers no matter what order they are registered in.  Pinning the salt an unpinned gene was
+ * handed keeps its header where it is from then on.
+ */
+pub fn assign_headers<const N: usize>(
+    requests: &[HeaderRequest],
+    min_distance: usize,
+) -> Result<Vec<AssignedHeader<N>>, HeaderAssignmentError> {
+    let mut order: Vec<usize> = (0..requests.len()).collect();
+    order.sort_by_key(|&i| requests[i].id.hash());
+    if let Some(pair) = order
+        .windows(2)
+        .find(|pair| requests[pair[0]].id == requests[pair[1]].id)
+    {
+        return Err(HeaderAssignmentError::DuplicateId(requests[pair[0]].id));
+    }
+    // Pinned genes go first, nothing can move them
+    order.sort_by_key(|&i| requests[i].salt.is_none());
+
+    let mut headers: Vec<Option<AssignedHeader<N>>> = vec![None; requests.len()];
+    let mut assigned: Vec<(GeneId, [AminoAcid; N])> = Vec::with_capacity(requests.len());
+    for index in order {
+        let HeaderRequest { id, salt } = requests[index];
+        let far_enough = |candidate: &[AminoAcid; N]| {
+            assigned
+                .iter()
+                .find_map(|(other, header)| {
+                    let distance = generic_levenshtein::distance(candidate, header);
+                    (distance < min_distance).then_some((*other, distance))
+                })
+                .map_or(Ok(()), Err)
+        };
+        let found = match salt {
+            Some(salt) => {
+                let header = candidate_header::<N>(id.hash(), salt);
+                far_enough(&header).map_err(|(first, distance)| {
+                    HeaderAssignmentError::TooClose {
+                        first,
+                        second: id,
+                        distance,
+                    }
+                })?;
+                AssignedHeader { header, salt }
+            }
+            None => (0..MAX_ATTEMPTS)
+                .map(|salt| AssignedHeader {
+                    header: candidate_header::<N>(id.hash(), salt),
+                    salt,
+                })
+                .find(|candidate| far_enough(&candidate.header).is_ok())
+                .ok_or(HeaderAssignmentError::Exhausted { id, min_distance })?,
+        };
+        assigned.push((id, found.header));
+        headers[index] = Some(found);
+    }
+
+    Ok(headers
+        .into_iter()
+        .map(|h| h.expect("Every gene"))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AssignedHeader, GeneId, HeaderAssignmentError, HeaderRequest, assign_headers,
+        candidate_header,
+    };
+    use crate::{amino_acid::AminoAcid, dna::get_header};
+
+    fn unpinned(count: u32) -> Vec<HeaderRequest> {
+        (1..=count)
+            .map(|version| HeaderRequest {
+                id: GeneId::new("test", version),
+                salt: None,
+            })
+            .collect()
+    }
+
+    fn headers<const N: usize>(assigned: &[AssignedHeader<N>]) -> Vec<[AminoAcid; N]> {
+        assigned.iter().map(|assigned| assigned.header).collect()
+    }
+
+    #[test]
+    fn first_candidate_is_the_hash_header() {
+        const ID: GeneId = GeneId::new("activation", 1);
+        const HASH: u128 = ID.hash();
+        assert_eq!(candidate_header::<4>(HASH, 0), get_header::<4, 12, HASH>());
+        assert_eq!(
+            assign_headers::<4>(&[HeaderRequest { id: ID, salt: None }], 3).unwrap(),
+            vec![AssignedHeader {
+                header: get_header::<4, 12, HASH>(),
+                salt: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn headers_keep_their_distance() {
+        let headers = headers(&assign_headers::<4>(&unpinned(60), 3).unwrap());
+
+        for (i, a) in headers.iter().enumerate() {
+            for b in &headers[i + 1..] {
+                assert!(generic_levenshtein::distance(a, b) >= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn registration_order_does_not_matter() {
+        let requests = unpinned(20);
+        let mut reversed = requests.clone();
+        reversed.reverse();
+
+        let mut assigned = assign_headers::<5>(&requests, 4).unwrap();
+        assigned.reverse();
+        assert_eq!(assign_headers::<5>(&reversed, 4).unwrap(), assigned);
+    }
+
+    #[test]
+    fn pinned_headers_never_move() {
+        let mut requests = unpinned(20);
+        let assigned = assign_headers::<4>(&requests, 3).unwrap();
+        for (request, assigned) in requests.iter_mut().zip(&assigned) {
+            request.salt = Some(assigned.salt);
+        }
+
+        // Unpinned, genes with lower hashes are served first and push the others around
+        let mut more = unpinned(60);
+        assert_ne!(assign_headers::<4>(&more, 3).unwrap()[..20], assigned[..]);
+        more.drain(..20);
+        more.extend_from_slice(&requests);
+        let grown = assign_headers::<4>(&more, 3).unwrap();
+        assert_eq!(grown[40..], assigned[..]);
+
+        let headers = headers(&grown);
+        for (i, a) in headers.iter().enumerate() {
+            for b in &headers[i + 1..] {
+                assert!(generic_levenshtein::distance(a, b) >= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn impossible_assignments_fail() {
+        // No two headers of 4 can be more than 4 edits apart
+        let mut requests = unpinned(2);
+        assert!(matches!(
+            assign_headers::<4>(&requests, 5),
+            Err(HeaderAssignmentError::Exhausted {
+                min_distance: 5,
+                ..
+            })
+        ));
+
+        for request in &mut requests {
+            request.salt = Some(0);
+        }
+        assert!(matches!(
+            assign_headers::<4>(&requests, 5),
+            Err(HeaderAssignmentError::TooClose { .. })
+        ));
+
+        assert_eq!(
+            assign_headers::<4>(&[requests[0], requests[1], requests[0]], 1),
+            Err(HeaderAssignmentError::DuplicateId(requests[0].id))
+        );
+    }
+
+    #[test]
+    fn gene_ids_hash_apart() {
+        const ACTIVATION: GeneId = GeneId::new("activation", 1);
+        const HASH: u128 = ACTIVATION.hash();
+
+        assert_eq!(HASH, GeneId::new("activation", 1).hash());
+        assert_ne!(HASH, GeneId::new("activation", 2).hash());
+        assert_ne!(HASH, GeneId::new("activatioN", 1).hash());
+        assert_eq!(ACTIVATION.to_string(), "activation@1");
+    }
+}
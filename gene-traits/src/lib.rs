@@ -1,5 +1,8 @@
+pub mod header;
+pub mod mutation;
+pub mod packed;
+
 pub mod dna {
-    use hashed_type_def::HashedTypeDef;
     use quote::quote;
     use rand::Rng;
 
@@ -21,42 +24,61 @@ pub mod dna {
                 _ => Nucleotide::G,
             }
         }
+
+        // The base this one pairs with on the opposite strand
+        pub const fn complement(self) -> Nucleotide {
+            match self {
+                Nucleotide::A => Nucleotide::T,
+                Nucleotide::C => Nucleotide::G,
+                Nucleotide::T => Nucleotide::A,
+                Nucleotide::G => Nucleotide::C,
+            }
+        }
     }
 
-    pub const fn get_hash<T: HashedTypeDef>() -> u128 {
-        T::TYPE_HASH_NATIVE
+    // The hash only has 16 bytes, headers longer than 5 amino acids take the rest of their bytes
+    // from remixed copies of the hash.  The first 16 bytes are the hash itself, so short headers
+    // stay the same.
+    pub(crate) const fn hash_byte(hash: u128, index: usize) -> u128 {
+        let mut mixed = hash;
+        let mut round = 0;
+        while round < index / 16 {
+            mixed ^= mixed >> 67;
+            mixed = mixed.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+            mixed ^= mixed >> 61;
+            round += 1;
+        }
+        mixed >> ((index % 16) * 8)
     }
 
     pub const fn get_header<const N: usize, const U: usize, const HASH: u128>() -> [AminoAcid; N] {
+        header_from_hash::<N>(HASH)
+    }
+
+    pub const fn header_from_hash<const N: usize>(hash: u128) -> [AminoAcid; N] {
         let mut arr = [AminoAcid::A; N];
-        let mut nuc_arr = [Nucleotide::A; U];
-        let hash = HASH;
         let mut i = 0;
-        while i < U {
-            // Ok, I feel like I need to explain this, since it took so long to get here.
-            // The hash needs to be shifted by a byte each loop and then masked with 2 bits.
-            // Those 2 bits represent the DNA Nucleotide.
-            nuc_arr[i] = match ((hash >> (i * 8)) & 0x3) % 4 {
-                0 => Nucleotide::A,
-                1 => Nucleotide::C,
-                2 => Nucleotide::T,
-                _ => Nucleotide::G,
-            };
-            i += 1;
-        }
-
-        i = 0;
         while i < N {
             let mut rna_part: [rna::Nucleotide; 3] = [rna::Nucleotide::U; 3];
 
             let mut j = 0;
             while j < 3 {
-                rna_part[j] = rna::from_dna(nuc_arr[i*3 + j]);
+                // Ok, I feel like I need to explain this, since it took so long to get here.
+                // The hash needs to be shifted by a byte each loop and then masked with 2 bits.
+                // Those 2 bits represent the DNA Nucleotide.
+                let nucleotide = match (hash_byte(hash, i * 3 + j) & 0x3) % 4 {
+                    0 => Nucleotide::A,
+                    1 => Nucleotide::C,
+                    2 => Nucleotide::T,
+                    _ => Nucleotide::G,
+                };
+                rna_part[j] = rna::from_dna(nucleotide);
                 j += 1;
             }
             let next_triple = amino_acid::from_rna_triple(rna_part);
+            // Headers may only hold real amino acids, a stop in the middle of one would end the gene
             match next_triple {
-                AminoAcid::UNKNOWN => { arr[i] = AminoAcid::A; },
+                AminoAcid::UNKNOWN | AminoAcid::START | AminoAcid::STOP => { arr[i] = AminoAcid::A; },
                 _ => arr[i] = next_triple
             }
 
@@ -65,21 +87,31 @@ pub mod dna {
         arr
     }
 
-    #[macro_export]
-    macro_rules! mul {
-        ($a:expr, $b:expr) => {
-            $a * $b
+    impl From<Nucleotide> for char {
+        fn from(value: Nucleotide) -> Self {
+            match value {
+                Nucleotide::A => 'A',
+                Nucleotide::C => 'C',
+                Nucleotide::T => 'T',
+                Nucleotide::G => 'G',
+            }
         }
     }
-    #[macro_export]
-    macro_rules! register_gene {
-    ($ty:ty,$hash:expr,$parser:ident,$promoter_size:expr) => {
-        inventory::submit!{ ComponentRegister { header: get_header::<$promoter_size,{mul!($promoter_size ,3)},$hash>(), type_hash:$hash, type_str: stringify!($ty), parser: $parser}}
-    };
-    ($ty:ty,$hash:expr,$parser:ident<$($base_pair_type:ty),*>,$promoter_size:expr) => {
-        inventory::submit!{ ComponentRegister { header: get_header::<$promoter_size,{mul!($promoter_size,3)},$hash>(), type_hash: $hash, type_str: stringify!($ty), parser: $parser::<$($base_pair_type),*>}}
+
+    // Lower case is accepted, anything else is handed back as the error
+    impl TryFrom<char> for Nucleotide {
+        type Error = char;
+
+        fn try_from(value: char) -> Result<Self, Self::Error> {
+            match value.to_ascii_uppercase() {
+                'A' => Ok(Nucleotide::A),
+                'C' => Ok(Nucleotide::C),
+                'T' => Ok(Nucleotide::T),
+                'G' => Ok(Nucleotide::G),
+                _ => Err(value),
+            }
+        }
     }
-}
 
     impl quote::ToTokens for Nucleotide {
         fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
@@ -115,6 +147,18 @@ pub mod rna {
         }
     }
 
+    // Reverse transcription, the way back from a strand to the genome it was read from
+    impl From<Nucleotide> for dna::Nucleotide {
+        fn from(value: Nucleotide) -> Self {
+            match value {
+                Nucleotide::A => dna::Nucleotide::A,
+                Nucleotide::C => dna::Nucleotide::C,
+                Nucleotide::U => dna::Nucleotide::T,
+                Nucleotide::G => dna::Nucleotide::G,
+            }
+        }
+    }
+
     pub const fn from_dna(value: dna::Nucleotide) -> Nucleotide {
         match value {
             dna::Nucleotide::A => Nucleotide::A,
@@ -123,12 +167,37 @@ pub mod rna {
             dna::Nucleotide::G => Nucleotide::G,
         }
     }
+
+    impl From<Nucleotide> for char {
+        fn from(value: Nucleotide) -> Self {
+            match value {
+                Nucleotide::A => 'A',
+                Nucleotide::C => 'C',
+                Nucleotide::U => 'U',
+                Nucleotide::G => 'G',
+            }
+        }
+    }
+
+    impl TryFrom<char> for Nucleotide {
+        type Error = char;
+
+        fn try_from(value: char) -> Result<Self, Self::Error> {
+            match value.to_ascii_uppercase() {
+                'A' => Ok(Nucleotide::A),
+                'C' => Ok(Nucleotide::C),
+                'U' => Ok(Nucleotide::U),
+                'G' => Ok(Nucleotide::G),
+                _ => Err(value),
+            }
+        }
+    }
 }
 
 pub mod amino_acid {
     use crate::rna;
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum AminoAcid {
         A,
         R,
@@ -150,174 +219,473 @@ pub mod amino_acid {
         W,
         Y,
         V,
+        // A codon the genetic code has no amino acid for
         UNKNOWN,
+        // Translation begins after this codon and reads in its frame
+        START,
+        // Ends translation, and with it the gene
+        STOP,
     }
 
-    pub const fn from_rna_triple(value: [rna::Nucleotide; 3]) -> AminoAcid {
-        match value {
-            [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::A] => AminoAcid::A,
-            [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::C] => AminoAcid::A,
-            [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::U] => AminoAcid::UNKNOWN,
-            [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::G] => AminoAcid::A,
-            [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::A] => AminoAcid::R,
-            [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::C] => AminoAcid::R,
-            [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::U] => AminoAcid::R,
-            [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::G] => AminoAcid::N,
-            [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::A] => AminoAcid::N,
-            [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::C] => AminoAcid::N,
-            [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::U] => AminoAcid::UNKNOWN,
-            [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::G] => AminoAcid::D,
-            [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::A] => AminoAcid::D,
-            [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::C] => AminoAcid::D,
-            [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::U] => AminoAcid::C,
-            [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::G] => AminoAcid::C,
-            [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::A] => AminoAcid::C,
-            [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::C] => AminoAcid::Q,
-            [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::U] => AminoAcid::Q,
-            [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::G] => AminoAcid::Q,
-            [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::A] => AminoAcid::E,
-            [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::C] => AminoAcid::E,
-            [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::U] => AminoAcid::E,
-            [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::G] => AminoAcid::G,
-            [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::A] => AminoAcid::G,
-            [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::C] => AminoAcid::G,
-            [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::U] => AminoAcid::H,
-            [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::G] => AminoAcid::H,
-            [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::A] => AminoAcid::H,
-            [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::C] => AminoAcid::I,
-            [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::U] => AminoAcid::I,
-            [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::G] => AminoAcid::I,
-            [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::A] => AminoAcid::L,
-            [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::C] => AminoAcid::L,
-            [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::U] => AminoAcid::L,
-            [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::G] => AminoAcid::K,
-            [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::A] => AminoAcid::K,
-            [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::C] => AminoAcid::K,
-            [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::U] => AminoAcid::M,
-            [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::G] => AminoAcid::M,
-            [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::A] => AminoAcid::UNKNOWN,
-            [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::C] => AminoAcid::M,
-            [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::U] => AminoAcid::UNKNOWN,
-            [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::G] => AminoAcid::F,
-            [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::A] => AminoAcid::F,
-            [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::C] => AminoAcid::F,
-            [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::U] => AminoAcid::P,
-            [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::G] => AminoAcid::P,
-            [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::A] => AminoAcid::P,
-            [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::C] => AminoAcid::S,
-            [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::U] => AminoAcid::S,
-            [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::G] => AminoAcid::S,
-            [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::A] => AminoAcid::T,
-            [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::C] => AminoAcid::T,
-            [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::U] => AminoAcid::T,
-            [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::G] => AminoAcid::W,
-            [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::A] => AminoAcid::W,
-            [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::C] => AminoAcid::W,
-            [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::U] => AminoAcid::Y,
-            [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::G] => AminoAcid::Y,
-            [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::A] => AminoAcid::Y,
-            [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::C] => AminoAcid::V,
-            [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::U] => AminoAcid::V,
-            [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::G] => AminoAcid::V,
+    impl AminoAcid {
+        // True for the codons that only steer translation and carry no value
+        pub const fn is_signal(&self) -> bool {
+            matches!(self, AminoAcid::START | AminoAcid::STOP)
         }
+    }
 
+    pub const fn from_rna_triple(value: [rna::Nucleotide; 3]) -> AminoAcid {
+        GeneticCode::STANDARD.translate(value)
     }
+
     impl From<[rna::Nucleotide; 3]> for AminoAcid {
         fn from(value: [rna::Nucleotide; 3]) -> Self {
-            match value {
-                [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::A] => Self::A,
-                [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::C] => Self::A,
-                [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::U] => Self::UNKNOWN,
-                [rna::Nucleotide::A, rna::Nucleotide::A, rna::Nucleotide::G] => Self::A,
-                [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::A] => Self::R,
-                [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::C] => Self::R,
-                [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::U] => Self::R,
-                [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::G] => Self::N,
-                [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::A] => Self::N,
-                [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::C] => Self::N,
-                [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::U] => Self::UNKNOWN,
-                [rna::Nucleotide::A, rna::Nucleotide::U, rna::Nucleotide::G] => Self::D,
-                [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::A] => Self::D,
-                [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::C] => Self::D,
-                [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::U] => Self::C,
-                [rna::Nucleotide::A, rna::Nucleotide::G, rna::Nucleotide::G] => Self::C,
-                [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::A] => Self::C,
-                [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::C] => Self::Q,
-                [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::U] => Self::Q,
-                [rna::Nucleotide::C, rna::Nucleotide::A, rna::Nucleotide::G] => Self::Q,
-                [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::A] => Self::E,
-                [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::C] => Self::E,
-                [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::U] => Self::E,
-                [rna::Nucleotide::C, rna::Nucleotide::C, rna::Nucleotide::G] => Self::G,
-                [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::A] => Self::G,
-                [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::C] => Self::G,
-                [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::U] => Self::H,
-                [rna::Nucleotide::C, rna::Nucleotide::U, rna::Nucleotide::G] => Self::H,
-                [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::A] => Self::H,
-                [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::C] => Self::I,
-                [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::U] => Self::I,
-                [rna::Nucleotide::C, rna::Nucleotide::G, rna::Nucleotide::G] => Self::I,
-                [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::A] => Self::L,
-                [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::C] => Self::L,
-                [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::U] => Self::L,
-                [rna::Nucleotide::U, rna::Nucleotide::A, rna::Nucleotide::G] => Self::K,
-                [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::A] => Self::K,
-                [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::C] => Self::K,
-                [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::U] => Self::M,
-                [rna::Nucleotide::U, rna::Nucleotide::C, rna::Nucleotide::G] => Self::M,
-                [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::A] => Self::UNKNOWN,
-                [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::C] => Self::M,
-                [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::U] => Self::UNKNOWN,
-                [rna::Nucleotide::U, rna::Nucleotide::U, rna::Nucleotide::G] => Self::F,
-                [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::A] => Self::F,
-                [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::C] => Self::F,
-                [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::U] => Self::P,
-                [rna::Nucleotide::U, rna::Nucleotide::G, rna::Nucleotide::G] => Self::P,
-                [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::A] => Self::P,
-                [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::C] => Self::S,
-                [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::U] => Self::S,
-                [rna::Nucleotide::G, rna::Nucleotide::A, rna::Nucleotide::G] => Self::S,
-                [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::A] => Self::T,
-                [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::C] => Self::T,
-                [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::U] => Self::T,
-                [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::G] => Self::W,
-                [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::A] => Self::W,
-                [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::C] => Self::W,
-                [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::U] => Self::Y,
-                [rna::Nucleotide::G, rna::Nucleotide::U, rna::Nucleotide::G] => Self::Y,
-                [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::A] => Self::Y,
-                [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::C] => Self::V,
-                [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::U] => Self::V,
-                [rna::Nucleotide::G, rna::Nucleotide::G, rna::Nucleotide::G] => Self::V,
+            from_rna_triple(value)
+        }
+    }
+
+    const RNA_BASES: [rna::Nucleotide; 4] = [
+        rna::Nucleotide::A,
+        rna::Nucleotide::C,
+        rna::Nucleotide::U,
+        rna::Nucleotide::G,
+    ];
+
+    /**
+     * Maps every codon to an amino acid.  The table is indexed with codon_index, so the first
+     * base picks a block of 16, the second a row of 4 and the third the entry, each in the order
+     * A, C, U, G.
+     */
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GeneticCode {
+        table: [AminoAcid; 64],
+    }
+
+    impl Default for GeneticCode {
+        fn default() -> Self {
+            Self::STANDARD
+        }
+    }
+
+    impl GeneticCode {
+        // The code every header and gene parser was written against
+        pub const STANDARD: GeneticCode = GeneticCode::new([
+            AminoAcid::START, AminoAcid::A, AminoAcid::A, AminoAcid::A, // AA*
+            AminoAcid::R, AminoAcid::R, AminoAcid::R, AminoAcid::N, // AC*
+            AminoAcid::N, AminoAcid::N, AminoAcid::STOP, AminoAcid::D, // AU*
+            AminoAcid::D, AminoAcid::D, AminoAcid::C, AminoAcid::C, // AG*
+            AminoAcid::C, AminoAcid::Q, AminoAcid::Q, AminoAcid::Q, // CA*
+            AminoAcid::E, AminoAcid::E, AminoAcid::E, AminoAcid::G, // CC*
+            AminoAcid::G, AminoAcid::G, AminoAcid::H, AminoAcid::H, // CU*
+            AminoAcid::H, AminoAcid::I, AminoAcid::I, AminoAcid::I, // CG*
+            AminoAcid::L, AminoAcid::L, AminoAcid::L, AminoAcid::K, // UA*
+            AminoAcid::K, AminoAcid::K, AminoAcid::M, AminoAcid::M, // UC*
+            AminoAcid::STOP, AminoAcid::M, AminoAcid::STOP, AminoAcid::F, // UU*
+            AminoAcid::F, AminoAcid::F, AminoAcid::P, AminoAcid::P, // UG*
+            AminoAcid::P, AminoAcid::S, AminoAcid::S, AminoAcid::S, // GA*
+            AminoAcid::T, AminoAcid::T, AminoAcid::T, AminoAcid::W, // GC*
+            AminoAcid::W, AminoAcid::W, AminoAcid::Y, AminoAcid::Y, // GU*
+            AminoAcid::Y, AminoAcid::V, AminoAcid::V, AminoAcid::V, // GG*
+        ]);
+
+        // Only the first two bases of a codon matter, so every change to the third base is silent.
+        // The price is that only 14 amino acids can be encoded.
+        pub const WOBBLE: GeneticCode = GeneticCode::from_prefixes([
+            AminoAcid::A, AminoAcid::R, AminoAcid::START, AminoAcid::D, // A*
+            AminoAcid::C, AminoAcid::Q, AminoAcid::E, AminoAcid::G, // C*
+            AminoAcid::L, AminoAcid::K, AminoAcid::STOP, AminoAcid::F, // U*
+            AminoAcid::S, AminoAcid::T, AminoAcid::Y, AminoAcid::V, // G*
+        ]);
+
+        pub const fn new(table: [AminoAcid; 64]) -> Self {
+            Self { table }
+        }
+
+        const fn from_prefixes(prefixes: [AminoAcid; 16]) -> Self {
+            let mut table = [AminoAcid::UNKNOWN; 64];
+            let mut i = 0;
+            while i < 64 {
+                table[i] = prefixes[i / 4];
+                i += 1;
             }
+            Self { table }
+        }
+
+        pub const fn codon_index(codon: [rna::Nucleotide; 3]) -> usize {
+            const fn base_index(base: rna::Nucleotide) -> usize {
+                match base {
+                    rna::Nucleotide::A => 0,
+                    rna::Nucleotide::C => 1,
+                    rna::Nucleotide::U => 2,
+                    rna::Nucleotide::G => 3,
+                }
+            }
+            base_index(codon[0]) * 16 + base_index(codon[1]) * 4 + base_index(codon[2])
+        }
+
+        pub const fn codon(index: usize) -> [rna::Nucleotide; 3] {
+            [
+                RNA_BASES[(index / 16) % 4],
+                RNA_BASES[(index / 4) % 4],
+                RNA_BASES[index % 4],
+            ]
+        }
+
+        pub const fn translate(&self, codon: [rna::Nucleotide; 3]) -> AminoAcid {
+            self.table[Self::codon_index(codon)]
+        }
+
+        pub fn set(&mut self, codon: [rna::Nucleotide; 3], amino_acid: AminoAcid) {
+            self.table[Self::codon_index(codon)] = amino_acid;
         }
+
+        // Every codon that translates to the amino acid, in table order
+        pub fn codons_for(&self, amino_acid: AminoAcid) -> Vec<[rna::Nucleotide; 3]> {
+            (0..64)
+                .filter(|&i| self.table[i] == amino_acid)
+                .map(Self::codon)
+                .collect()
+        }
+
+        /**
+         * Reads a code from text with one codon per line, e.g. "AUG M".  Codons may be written
+         * with T or U, X stands for an untranslatable codon and '#' starts a comment.
+         * Every one of the 64 codons has to be given exactly once.
+         */
+        pub fn parse(text: &str) -> Result<Self, GeneticCodeError> {
+            let mut table = [None; 64];
+            for (index, line) in text.lines().enumerate() {
+                let line_number = index + 1;
+                let content = line.split('#').next().unwrap_or("").trim();
+                if content.is_empty() {
+                    continue;
+                }
+                let mut words = content.split_whitespace();
+                let (Some(codon), Some(amino_acid), None) = (words.next(), words.next(), words.next())
+                else {
+                    return Err(GeneticCodeError::InvalidLine { line: line_number });
+                };
+
+                let codon: Vec<rna::Nucleotide> = codon
+                    .chars()
+                    .map(|c| match c.to_ascii_uppercase() {
+                        'T' => Ok(rna::Nucleotide::U),
+                        c => rna::Nucleotide::try_from(c),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| GeneticCodeError::InvalidLine { line: line_number })?;
+                let codon: [rna::Nucleotide; 3] = codon
+                    .try_into()
+                    .map_err(|_| GeneticCodeError::InvalidLine { line: line_number })?;
+
+                let mut letters = amino_acid.chars();
+                let amino_acid = match (letters.next(), letters.next()) {
+                    (Some(letter), None) => AminoAcid::try_from(letter)
+                        .map_err(|_| GeneticCodeError::InvalidLine { line: line_number })?,
+                    _ => return Err(GeneticCodeError::InvalidLine { line: line_number }),
+                };
+
+                let entry = &mut table[Self::codon_index(codon)];
+                if entry.is_some() {
+                    return Err(GeneticCodeError::DuplicateCodon {
+                        line: line_number,
+                        codon,
+                    });
+                }
+                *entry = Some(amino_acid);
+            }
+
+            let mut code = [AminoAcid::UNKNOWN; 64];
+            for (index, entry) in table.into_iter().enumerate() {
+                code[index] = entry.ok_or(GeneticCodeError::MissingCodon {
+                    codon: Self::codon(index),
+                })?;
+            }
+            Ok(Self::new(code))
+        }
+
+        pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, GeneticCodeError> {
+            Self::parse(&std::fs::read_to_string(path)?)
+        }
+    }
+
+    // Writes the same format parse reads
+    impl std::fmt::Display for GeneticCode {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for (index, amino_acid) in self.table.iter().enumerate() {
+                let codon: String = Self::codon(index).into_iter().map(char::from).collect();
+                writeln!(f, "{} {}", codon, char::from(*amino_acid))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum GeneticCodeError {
+        Io(std::io::Error),
+        // Lines are counted from 1
+        InvalidLine { line: usize },
+        DuplicateCodon {
+            line: usize,
+            codon: [rna::Nucleotide; 3],
+        },
+        MissingCodon { codon: [rna::Nucleotide; 3] },
     }
 
-    impl Into<u8> for AminoAcid {
-        fn into(self) -> u8 {
+    impl std::fmt::Display for GeneticCodeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             match self {
-                AminoAcid::A => 0,
-                AminoAcid::R => 1,
-                AminoAcid::N => 2,
-                AminoAcid::D => 3,
-                AminoAcid::C => 4,
-                AminoAcid::Q => 5,
-                AminoAcid::E => 6,
-                AminoAcid::G => 7,
-                AminoAcid::H => 8,
-                AminoAcid::I => 9,
-                AminoAcid::L => 10,
-                AminoAcid::K => 11,
-                AminoAcid::M => 12,
-                AminoAcid::F => 13,
-                AminoAcid::P => 14,
-                AminoAcid::S => 15,
-                AminoAcid::T => 16,
-                AminoAcid::W => 17,
-                AminoAcid::Y => 18,
-                AminoAcid::V => 19,
-                AminoAcid::UNKNOWN => 0,
+                GeneticCodeError::Io(e) => write!(f, "{}", e),
+                GeneticCodeError::InvalidLine { line } => {
+                    write!(f, "expected a codon and an amino acid at line {}", line)
+                }
+                GeneticCodeError::DuplicateCodon { line, codon } => {
+                    write!(f, "codon {:?} is given a second time at line {}", codon, line)
+                }
+                GeneticCodeError::MissingCodon { codon } => {
+                    write!(f, "codon {:?} has no amino acid", codon)
+                }
             }
         }
     }
+
+    impl std::error::Error for GeneticCodeError {}
+
+    impl From<std::io::Error> for GeneticCodeError {
+        fn from(value: std::io::Error) -> Self {
+            GeneticCodeError::Io(value)
+        }
+    }
+
+    // One letter codes, with the usual X for an amino acid that couldn't be determined and * for a
+    // stop.  There is no usual letter for a start that isn't also methionine, so ^ is used.
+    impl From<AminoAcid> for char {
+        fn from(value: AminoAcid) -> Self {
+            match value {
+                AminoAcid::A => 'A',
+                AminoAcid::R => 'R',
+                AminoAcid::N => 'N',
+                AminoAcid::D => 'D',
+                AminoAcid::C => 'C',
+                AminoAcid::Q => 'Q',
+                AminoAcid::E => 'E',
+                AminoAcid::G => 'G',
+                AminoAcid::H => 'H',
+                AminoAcid::I => 'I',
+                AminoAcid::L => 'L',
+                AminoAcid::K => 'K',
+                AminoAcid::M => 'M',
+                AminoAcid::F => 'F',
+                AminoAcid::P => 'P',
+                AminoAcid::S => 'S',
+                AminoAcid::T => 'T',
+                AminoAcid::W => 'W',
+                AminoAcid::Y => 'Y',
+                AminoAcid::V => 'V',
+                AminoAcid::UNKNOWN => 'X',
+                AminoAcid::START => '^',
+                AminoAcid::STOP => '*',
+            }
+        }
+    }
+
+    impl TryFrom<char> for AminoAcid {
+        type Error = char;
+
+        fn try_from(value: char) -> Result<Self, Self::Error> {
+            match value.to_ascii_uppercase() {
+                'A' => Ok(AminoAcid::A),
+                'R' => Ok(AminoAcid::R),
+                'N' => Ok(AminoAcid::N),
+                'D' => Ok(AminoAcid::D),
+                'C' => Ok(AminoAcid::C),
+                'Q' => Ok(AminoAcid::Q),
+                'E' => Ok(AminoAcid::E),
+                'G' => Ok(AminoAcid::G),
+                'H' => Ok(AminoAcid::H),
+                'I' => Ok(AminoAcid::I),
+                'L' => Ok(AminoAcid::L),
+                'K' => Ok(AminoAcid::K),
+                'M' => Ok(AminoAcid::M),
+                'F' => Ok(AminoAcid::F),
+                'P' => Ok(AminoAcid::P),
+                'S' => Ok(AminoAcid::S),
+                'T' => Ok(AminoAcid::T),
+                'W' => Ok(AminoAcid::W),
+                'Y' => Ok(AminoAcid::Y),
+                'V' => Ok(AminoAcid::V),
+                'X' => Ok(AminoAcid::UNKNOWN),
+                '^' => Ok(AminoAcid::START),
+                '*' => Ok(AminoAcid::STOP),
+                _ => Err(value),
+            }
+        }
+    }
+
+    // Each amino acid stands for a value from 0 to 19.  Unknown codons and the start and stop
+    // signals have no value and are handed back as the error.
+    impl TryFrom<AminoAcid> for u8 {
+        type Error = AminoAcid;
+
+        fn try_from(value: AminoAcid) -> Result<Self, Self::Error> {
+            match value {
+                AminoAcid::A => Ok(0),
+                AminoAcid::R => Ok(1),
+                AminoAcid::N => Ok(2),
+                AminoAcid::D => Ok(3),
+                AminoAcid::C => Ok(4),
+                AminoAcid::Q => Ok(5),
+                AminoAcid::E => Ok(6),
+                AminoAcid::G => Ok(7),
+                AminoAcid::H => Ok(8),
+                AminoAcid::I => Ok(9),
+                AminoAcid::L => Ok(10),
+                AminoAcid::K => Ok(11),
+                AminoAcid::M => Ok(12),
+                AminoAcid::F => Ok(13),
+                AminoAcid::P => Ok(14),
+                AminoAcid::S => Ok(15),
+                AminoAcid::T => Ok(16),
+                AminoAcid::W => Ok(17),
+                AminoAcid::Y => Ok(18),
+                AminoAcid::V => Ok(19),
+                AminoAcid::UNKNOWN | AminoAcid::START | AminoAcid::STOP => Err(value),
+            }
+        }
+    }
+
+    // The amino acid that stands for a value, values past 19 are handed back as the error
+    impl TryFrom<u8> for AminoAcid {
+        type Error = u8;
+
+        fn try_from(value: u8) -> Result<Self, Self::Error> {
+            const VALUES: [AminoAcid; 20] = [
+                AminoAcid::A,
+                AminoAcid::R,
+                AminoAcid::N,
+                AminoAcid::D,
+                AminoAcid::C,
+                AminoAcid::Q,
+                AminoAcid::E,
+                AminoAcid::G,
+                AminoAcid::H,
+                AminoAcid::I,
+                AminoAcid::L,
+                AminoAcid::K,
+                AminoAcid::M,
+                AminoAcid::F,
+                AminoAcid::P,
+                AminoAcid::S,
+                AminoAcid::T,
+                AminoAcid::W,
+                AminoAcid::Y,
+                AminoAcid::V,
+            ];
+            VALUES.get(value as usize).copied().ok_or(value)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{AminoAcid, GeneticCode, GeneticCodeError, from_rna_triple};
+        use crate::rna::Nucleotide;
+
+        #[test]
+        fn long_headers_extend_short_ones() {
+            const HASH: u128 = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210;
+            let short = crate::dna::get_header::<4, 12, HASH>();
+            let long = crate::dna::get_header::<12, 36, HASH>();
+
+            assert_eq!(&long[..4], &short);
+            assert!(long[5..].iter().any(|acid| *acid != AminoAcid::A));
+        }
+
+        #[test]
+        fn codon_index_round_trips() {
+            for index in 0..64 {
+                assert_eq!(GeneticCode::codon_index(GeneticCode::codon(index)), index);
+            }
+            assert_eq!(
+                GeneticCode::STANDARD.translate([Nucleotide::A, Nucleotide::C, Nucleotide::A]),
+                AminoAcid::R
+            );
+        }
+
+        #[test]
+        fn start_and_stop_are_not_values() {
+            assert_eq!(
+                from_rna_triple([Nucleotide::A, Nucleotide::A, Nucleotide::A]),
+                AminoAcid::START
+            );
+            for codon in [
+                [Nucleotide::A, Nucleotide::U, Nucleotide::U],
+                [Nucleotide::U, Nucleotide::U, Nucleotide::A],
+                [Nucleotide::U, Nucleotide::U, Nucleotide::U],
+            ] {
+                assert_eq!(from_rna_triple(codon), AminoAcid::STOP);
+            }
+
+            assert_eq!(u8::try_from(AminoAcid::A), Ok(0));
+            assert_eq!(u8::try_from(AminoAcid::V), Ok(19));
+            for value in 0..20 {
+                assert_eq!(u8::try_from(AminoAcid::try_from(value).unwrap()), Ok(value));
+            }
+            assert_eq!(AminoAcid::try_from(20), Err(20));
+            for signal in [AminoAcid::UNKNOWN, AminoAcid::START, AminoAcid::STOP] {
+                assert_eq!(u8::try_from(signal), Err(signal));
+                assert_eq!(AminoAcid::try_from(char::from(signal)), Ok(signal));
+            }
+        }
+
+        #[test]
+        fn wobble_ignores_the_third_base() {
+            for index in (0..64).step_by(4) {
+                let first = GeneticCode::WOBBLE.translate(GeneticCode::codon(index));
+                for offset in 1..4 {
+                    assert_eq!(
+                        GeneticCode::WOBBLE.translate(GeneticCode::codon(index + offset)),
+                        first
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn parse_reads_what_display_writes() {
+            let mut code = GeneticCode::STANDARD;
+            code.set([Nucleotide::G, Nucleotide::G, Nucleotide::G], AminoAcid::W);
+            let text = format!("# a tweaked code\n{}", code);
+
+            assert_eq!(GeneticCode::parse(&text).unwrap(), code);
+            assert_eq!(code.codons_for(AminoAcid::V).len(), 2);
+        }
+
+        #[test]
+        fn parse_reports_bad_tables() {
+            let text = GeneticCode::STANDARD.to_string();
+
+            let missing: String = text.lines().skip(1).map(|l| format!("{}\n", l)).collect();
+            assert!(matches!(
+                GeneticCode::parse(&missing),
+                Err(GeneticCodeError::MissingCodon {
+                    codon: [Nucleotide::A, Nucleotide::A, Nucleotide::A]
+                })
+            ));
+
+            let duplicate = format!("{}aat a\n", text);
+            assert!(matches!(
+                GeneticCode::parse(&duplicate),
+                Err(GeneticCodeError::DuplicateCodon { line: 65, .. })
+            ));
+
+            assert!(matches!(
+                GeneticCode::parse("AAB A"),
+                Err(GeneticCodeError::InvalidLine { line: 1 })
+            ));
+            assert!(matches!(
+                GeneticCode::parse("AAA AR"),
+                Err(GeneticCodeError::InvalidLine { line: 1 })
+            ));
+        }
+    }
 }
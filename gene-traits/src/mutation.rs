@@ -0,0 +1,467 @@
+use std::{
+    fmt::{self, Display},
+    hash::Hash,
+    ops::Range,
+};
+
+use rand::Rng;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+
+use crate::{
+    dna::Nucleotide,
+    packed::{NucleotideSequence, PackedSequence},
+};
+
+// The edits the mutation operators are built from, so plain and packed genomes can both be mutated
+pub trait MutableSequence: NucleotideSequence {
+    fn set_nucleotide(&mut self, index: usize, nucleotide: Nucleotide);
+    fn insert_segment(&mut self, index: usize, segment: &[Nucleotide]);
+    fn remove_segment(&mut self, range: Range<usize>) -> Vec<Nucleotide>;
+}
+
+impl MutableSequence for Vec<Nucleotide> {
+    fn set_nucleotide(&mut self, index: usize, nucleotide: Nucleotide) {
+        self[index] = nucleotide;
+    }
+
+    fn insert_segment(&mut self, index: usize, segment: &[Nucleotide]) {
+        self.splice(index..index, segment.iter().copied());
+    }
+
+    fn remove_segment(&mut self, range: Range<usize>) -> Vec<Nucleotide> {
+        self.drain(range).collect()
+    }
+}
+
+impl MutableSequence for PackedSequence {
+    fn set_nucleotide(&mut self, index: usize, nucleotide: Nucleotide) {
+        self.set(index, nucleotide);
+    }
+
+    fn insert_segment(&mut self, index: usize, segment: &[Nucleotide]) {
+        self.splice(index, segment);
+    }
+
+    fn remove_segment(&mut self, range: Range<usize>) -> Vec<Nucleotide> {
+        self.drain(range)
+    }
+}
+
+/**
+ * The chance of each operator firing.  Substitution, insertion and deletion are rolled once for
+ * every base, the segment operators are rolled once for every call to mutate.
+ * All rates must be between 0 and 1, Mutator::new refuses anything else.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationRates {
+    pub substitution: f64,
+    pub insertion: f64,
+    pub deletion: f64,
+    pub duplication: f64,
+    pub inversion: f64,
+    pub translocation: f64,
+    // Upper bound on how many bases a segment operator works on
+    pub max_segment_length: usize,
+}
+
+impl Default for MutationRates {
+    fn default() -> Self {
+        Self {
+            substitution: 0.0,
+            insertion: 0.0,
+            deletion: 0.0,
+            duplication: 0.0,
+            inversion: 0.0,
+            translocation: 0.0,
+            max_segment_length: 12,
+        }
+    }
+}
+
+impl MutationRates {
+    // Every rate is a probability, anything outside 0 to 1 (or NaN) can't be rolled
+    pub fn validate(&self) -> Result<(), MutationRateError> {
+        let rates = [
+            ("substitution", self.substitution),
+            ("insertion", self.insertion),
+            ("deletion", self.deletion),
+            ("duplication", self.duplication),
+            ("inversion", self.inversion),
+            ("translocation", self.translocation),
+        ];
+        match rates.iter().find(|(_, rate)| !(0.0..=1.0).contains(rate)) {
+            Some(&(name, rate)) => Err(MutationRateError { name, rate }),
+            None => Ok(()),
+        }
+    }
+}
+
+// A rate that isn't a probability, named after its field in MutationRates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationRateError {
+    pub name: &'static str,
+    pub rate: f64,
+}
+
+impl Display for MutationRateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rate {} is not between 0 and 1", self.name, self.rate)
+    }
+}
+
+impl std::error::Error for MutationRateError {}
+
+/**
+ * A single change made to a genome.  Indices refer to the genome as it was right before the
+ * change, so replaying a log in order on the original genome gives the mutated one.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mutation {
+    Substitution {
+        index: usize,
+        from: Nucleotide,
+        to: Nucleotide,
+    },
+    Insertion {
+        index: usize,
+        nucleotide: Nucleotide,
+    },
+    Deletion {
+        index: usize,
+        nucleotide: Nucleotide,
+    },
+    // The copy of start..end is placed directly after the original
+    Duplication {
+        start: usize,
+        end: usize,
+    },
+    // start..end is replaced by its reverse complement
+    Inversion {
+        start: usize,
+        end: usize,
+    },
+    // start..end is cut out and inserted at insert_at of what is left
+    Translocation {
+        start: usize,
+        end: usize,
+        insert_at: usize,
+    },
+}
+
+impl Mutation {
+    pub fn apply<S: MutableSequence + ?Sized>(&self, genome: &mut S) {
+        match *self {
+            Mutation::Substitution { index, to, .. } => genome.set_nucleotide(index, to),
+            Mutation::Insertion { index, nucleotide } => {
+                genome.insert_segment(index, &[nucleotide])
+            }
+            Mutation::Deletion { index, .. } => {
+                genome.remove_segment(index..index + 1);
+            }
+            Mutation::Duplication { start, end } => {
+                let segment = genome.nucleotides(start..end);
+                genome.insert_segment(end, &segment);
+            }
+            Mutation::Inversion { start, end } => {
+                let segment = genome.nucleotides(start..end);
+                for (index, nucleotide) in (start..end).zip(segment.iter().rev()) {
+                    genome.set_nucleotide(index, nucleotide.complement());
+                }
+            }
+            Mutation::Translocation {
+                start,
+                end,
+                insert_at,
+            } => {
+                let segment = genome.remove_segment(start..end);
+                genome.insert_segment(insert_at, &segment);
+            }
+        }
+    }
+}
+
+/**
+ * Applies random mutations to genomes.  The rng is seeded so that an experiment can be rerun
+ * and produce exactly the same mutations.
+ */
+pub struct Mutator {
+    rates: MutationRates,
+    rng: Pcg64,
+}
+
+// Any hashable seed gives a reproducible rng, so experiments can be seeded with names as well as numbers
+pub fn seeded_rng<S: Hash>(seed: S) -> Pcg64 {
+    Seeder::from(seed).into_rng()
+}
+
+impl Mutator {
+    // Fails when a rate isn't a probability, the rates can't be changed afterwards
+    pub fn new<S: Hash>(seed: S, rates: MutationRates) -> Result<Self, MutationRateError> {
+        rates.validate()?;
+        Ok(Self {
+            rates,
+            rng: seeded_rng(seed),
+        })
+    }
+
+    pub fn rates(&self) -> &MutationRates {
+        &self.rates
+    }
+
+    pub fn mutate<S: MutableSequence + ?Sized>(&mut self, genome: &mut S) -> Vec<Mutation> {
+        let mut log = Vec::new();
+
+        let mut index = 0;
+        while index < genome.len() {
+            if self.rng.random_bool(self.rates.deletion) {
+                self.record(
+                    Mutation::Deletion {
+                        index,
+                        nucleotide: genome.nucleotide(index),
+                    },
+                    genome,
+                    &mut log,
+                );
+                continue;
+            }
+            if self.rng.random_bool(self.rates.substitution) {
+                let from = genome.nucleotide(index);
+                let to = loop {
+                    let to = Nucleotide::sample(&mut self.rng);
+                    if to != from {
+                        break to;
+                    }
+                };
+                self.record(Mutation::Substitution { index, from, to }, genome, &mut log);
+            }
+            if self.rng.random_bool(self.rates.insertion) {
+                let nucleotide = Nucleotide::sample(&mut self.rng);
+                self.record(Mutation::Insertion { index, nucleotide }, genome, &mut log);
+                // Step over the new base as well, it shouldn't get another roll
+                index += 1;
+            }
+            index += 1;
+        }
+
+        if self.rng.random_bool(self.rates.duplication)
+            && let Some((start, end)) = self.segment(genome)
+        {
+            self.record(Mutation::Duplication { start, end }, genome, &mut log);
+        }
+        if self.rng.random_bool(self.rates.inversion)
+            && let Some((start, end)) = self.segment(genome)
+        {
+            self.record(Mutation::Inversion { start, end }, genome, &mut log);
+        }
+        if self.rng.random_bool(self.rates.translocation)
+            && let Some((start, end)) = self.segment(genome)
+        {
+            let insert_at = self.rng.random_range(0..=genome.len() - (end - start));
+            self.record(
+                Mutation::Translocation {
+                    start,
+                    end,
+                    insert_at,
+                },
+                genome,
+                &mut log,
+            );
+        }
+
+        log
+    }
+
+    fn record<S: MutableSequence + ?Sized>(
+        &self,
+        mutation: Mutation,
+        genome: &mut S,
+        log: &mut Vec<Mutation>,
+    ) {
+        mutation.apply(genome);
+        log.push(mutation);
+    }
+
+    fn segment<S: MutableSequence + ?Sized>(&mut self, genome: &S) -> Option<(usize, usize)> {
+        if genome.is_empty() || self.rates.max_segment_length == 0 {
+            return None;
+        }
+        let start = self.rng.random_range(0..genome.len());
+        let max_length = self.rates.max_segment_length.min(genome.len() - start);
+        let length = self.rng.random_range(1..=max_length);
+        Some((start, start + length))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mutation, MutationRateError, MutationRates, Mutator};
+    use crate::{dna::Nucleotide, packed::PackedSequence};
+
+    fn genome() -> Vec<Nucleotide> {
+        [Nucleotide::A, Nucleotide::C, Nucleotide::G, Nucleotide::T].repeat(16)
+    }
+
+    fn all_rates() -> MutationRates {
+        MutationRates {
+            substitution: 0.05,
+            insertion: 0.05,
+            deletion: 0.05,
+            duplication: 0.5,
+            inversion: 0.5,
+            translocation: 0.5,
+            max_segment_length: 8,
+        }
+    }
+
+    #[test]
+    fn no_rates_no_mutations() {
+        let mut mutated = genome();
+        let log = Mutator::new(1, MutationRates::default())
+            .unwrap()
+            .mutate(&mut mutated);
+
+        assert!(log.is_empty());
+        assert_eq!(mutated, genome());
+    }
+
+    #[test]
+    fn rates_must_be_probabilities() {
+        let certain = MutationRates {
+            deletion: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(Mutator::new(1, certain).unwrap().rates(), &certain);
+
+        for (rates, name, rate) in [
+            (
+                MutationRates {
+                    insertion: 1.5,
+                    ..Default::default()
+                },
+                "insertion",
+                1.5,
+            ),
+            (
+                MutationRates {
+                    inversion: -0.1,
+                    ..Default::default()
+                },
+                "inversion",
+                -0.1,
+            ),
+        ] {
+            assert_eq!(
+                Mutator::new(1, rates).err(),
+                Some(MutationRateError { name, rate })
+            );
+        }
+        let nan = MutationRates {
+            substitution: f64::NAN,
+            ..Default::default()
+        };
+        assert!(Mutator::new(1, nan).is_err());
+    }
+
+    #[test]
+    fn same_seed_same_mutations() {
+        let mut first = genome();
+        let mut second = genome();
+
+        let first_log = Mutator::new("experiment", all_rates())
+            .unwrap()
+            .mutate(&mut first);
+        let second_log = Mutator::new("experiment", all_rates())
+            .unwrap()
+            .mutate(&mut second);
+
+        assert!(!first_log.is_empty());
+        assert_eq!(first_log, second_log);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn replaying_the_log_gives_the_mutated_genome() {
+        let mut mutator = Mutator::new(42, all_rates()).unwrap();
+        for _ in 0..10 {
+            let original = genome();
+            let mut mutated = original.clone();
+            let log = mutator.mutate(&mut mutated);
+
+            let mut replayed = original;
+            for mutation in &log {
+                mutation.apply(&mut replayed);
+            }
+            assert_eq!(replayed, mutated);
+        }
+    }
+
+    #[test]
+    fn substitution_always_changes_the_base() {
+        let mut mutated = genome();
+        let rates = MutationRates {
+            substitution: 1.0,
+            ..Default::default()
+        };
+        let log = Mutator::new(7, rates).unwrap().mutate(&mut mutated);
+
+        assert_eq!(log.len(), genome().len());
+        for (original, mutated) in genome().iter().zip(mutated.iter()) {
+            assert_ne!(original, mutated);
+        }
+    }
+
+    #[test]
+    fn inversion_is_reverse_complement() {
+        let mut sequence = vec![Nucleotide::A, Nucleotide::A, Nucleotide::C, Nucleotide::G];
+        Mutation::Inversion { start: 1, end: 4 }.apply(&mut sequence);
+
+        assert_eq!(
+            sequence,
+            vec![Nucleotide::A, Nucleotide::C, Nucleotide::G, Nucleotide::T]
+        );
+    }
+
+    #[test]
+    fn segment_operators() {
+        let sequence = vec![Nucleotide::A, Nucleotide::C, Nucleotide::G, Nucleotide::T];
+
+        let mut duplicated = sequence.clone();
+        Mutation::Duplication { start: 1, end: 3 }.apply(&mut duplicated);
+        assert_eq!(
+            duplicated,
+            vec![
+                Nucleotide::A,
+                Nucleotide::C,
+                Nucleotide::G,
+                Nucleotide::C,
+                Nucleotide::G,
+                Nucleotide::T,
+            ]
+        );
+
+        let mut translocated = sequence;
+        Mutation::Translocation {
+            start: 0,
+            end: 2,
+            insert_at: 2,
+        }
+        .apply(&mut translocated);
+        assert_eq!(
+            translocated,
+            vec![Nucleotide::G, Nucleotide::T, Nucleotide::A, Nucleotide::C]
+        );
+    }
+
+    #[test]
+    fn packed_genomes_mutate_like_plain_ones() {
+        let mut plain = genome();
+        let mut packed = PackedSequence::from(genome());
+
+        let plain_log = Mutator::new(9, all_rates()).unwrap().mutate(&mut plain);
+        let packed_log = Mutator::new(9, all_rates()).unwrap().mutate(&mut packed);
+
+        assert_eq!(plain_log, packed_log);
+        assert_eq!(packed.to_vec(), plain);
+    }
+}
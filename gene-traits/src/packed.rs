@@ -0,0 +1,496 @@
+use std::{
+    fmt::{self, Debug},
+    ops::{Bound, Range, RangeBounds},
+};
+
+use crate::dna::Nucleotide;
+
+// Read access shared by plain and packed sequences, so genome walkers don't care about the storage
+pub trait NucleotideSequence {
+    fn len(&self) -> usize;
+    fn nucleotide(&self, index: usize) -> Nucleotide;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn nucleotides(&self, range: Range<usize>) -> Vec<Nucleotide> {
+        range.map(|i| self.nucleotide(i)).collect()
+    }
+}
+
+impl NucleotideSequence for [Nucleotide] {
+    fn len(&self) -> usize {
+        <[Nucleotide]>::len(self)
+    }
+
+    fn nucleotide(&self, index: usize) -> Nucleotide {
+        self[index]
+    }
+}
+
+impl<const N: usize> NucleotideSequence for [Nucleotide; N] {
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn nucleotide(&self, index: usize) -> Nucleotide {
+        self[index]
+    }
+}
+
+impl NucleotideSequence for Vec<Nucleotide> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn nucleotide(&self, index: usize) -> Nucleotide {
+        self[index]
+    }
+}
+
+// Turns any range into start..end, panicking like slice indexing does when it doesn't fit
+fn resolve(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&s) => s,
+        Bound::Excluded(&s) => s + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&e) => e + 1,
+        Bound::Excluded(&e) => e,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start <= end && end <= len,
+        "range {}..{} out of range for {}",
+        start,
+        end,
+        len
+    );
+    start..end
+}
+
+const fn encode(nucleotide: Nucleotide) -> u8 {
+    match nucleotide {
+        Nucleotide::A => 0,
+        Nucleotide::C => 1,
+        Nucleotide::T => 2,
+        Nucleotide::G => 3,
+    }
+}
+
+const fn decode(bits: u8) -> Nucleotide {
+    match bits & 0x3 {
+        0 => Nucleotide::A,
+        1 => Nucleotide::C,
+        2 => Nucleotide::T,
+        _ => Nucleotide::G,
+    }
+}
+
+/**
+ * A nucleotide sequence stored at 2 bits a base, 4 bases to a byte.  Base i lives in byte i / 4,
+ * starting at the low bits.  Unused bits of the last byte are always kept at zero so that equal
+ * sequences have equal bytes.
+ */
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct PackedSequence {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl PackedSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity.div_ceil(4)),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn get(&self, index: usize) -> Option<Nucleotide> {
+        if index < self.len {
+            Some(decode(self.bytes[index / 4] >> ((index % 4) * 2)))
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, index: usize, nucleotide: Nucleotide) {
+        assert!(
+            index < self.len,
+            "index {} out of range for {}",
+            index,
+            self.len
+        );
+        let shift = (index % 4) * 2;
+        let byte = &mut self.bytes[index / 4];
+        *byte = (*byte & !(0x3 << shift)) | (encode(nucleotide) << shift);
+    }
+
+    pub fn push(&mut self, nucleotide: Nucleotide) {
+        if self.len.is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, nucleotide);
+    }
+
+    pub fn pop(&mut self) -> Option<Nucleotide> {
+        let last = self.get(self.len.checked_sub(1)?)?;
+        self.truncate(self.len - 1);
+        Some(last)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.len = len;
+        self.bytes.truncate(len.div_ceil(4));
+        if !len.is_multiple_of(4) {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] &= (1 << ((len % 4) * 2)) - 1;
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, nucleotide: Nucleotide) {
+        self.splice(index, &[nucleotide]);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Nucleotide {
+        self.drain(index..index + 1)[0]
+    }
+
+    // Inserts all of the segment before index
+    pub fn splice(&mut self, index: usize, segment: &[Nucleotide]) {
+        assert!(
+            index <= self.len,
+            "index {} out of range for {}",
+            index,
+            self.len
+        );
+        let tail = self.slice(index..).to_vec();
+        self.truncate(index);
+        self.extend(segment.iter().copied());
+        self.extend(tail);
+    }
+
+    // Removes the range and hands back what was in it
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Vec<Nucleotide> {
+        let range = resolve(range, self.len);
+        let removed = self.slice(range.clone()).to_vec();
+        let tail = self.slice(range.end..).to_vec();
+        self.truncate(range.start);
+        self.extend(tail);
+        removed
+    }
+
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> PackedSlice<'_> {
+        let range = resolve(range, self.len);
+        PackedSlice {
+            sequence: self,
+            start: range.start,
+            end: range.end,
+        }
+    }
+
+    pub fn as_slice(&self) -> PackedSlice<'_> {
+        self.slice(..)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.as_slice().iter()
+    }
+
+    pub fn windows<const N: usize>(&self) -> Windows<'_, N> {
+        self.as_slice().windows()
+    }
+
+    pub fn to_vec(&self) -> Vec<Nucleotide> {
+        self.iter().collect()
+    }
+}
+
+impl Debug for PackedSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+impl NucleotideSequence for PackedSequence {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn nucleotide(&self, index: usize) -> Nucleotide {
+        self.get(index).expect("index out of range")
+    }
+}
+
+impl Extend<Nucleotide> for PackedSequence {
+    fn extend<I: IntoIterator<Item = Nucleotide>>(&mut self, iter: I) {
+        for nucleotide in iter {
+            self.push(nucleotide);
+        }
+    }
+}
+
+impl FromIterator<Nucleotide> for PackedSequence {
+    fn from_iter<I: IntoIterator<Item = Nucleotide>>(iter: I) -> Self {
+        let mut sequence = PackedSequence::new();
+        sequence.extend(iter);
+        sequence
+    }
+}
+
+impl From<&[Nucleotide]> for PackedSequence {
+    fn from(value: &[Nucleotide]) -> Self {
+        value.iter().copied().collect()
+    }
+}
+
+impl From<Vec<Nucleotide>> for PackedSequence {
+    fn from(value: Vec<Nucleotide>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+impl From<&PackedSequence> for Vec<Nucleotide> {
+    fn from(value: &PackedSequence) -> Self {
+        value.to_vec()
+    }
+}
+
+impl<'a> IntoIterator for &'a PackedSequence {
+    type Item = Nucleotide;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// A borrowed view of part of a PackedSequence
+#[derive(Clone, Copy)]
+pub struct PackedSlice<'a> {
+    sequence: &'a PackedSequence,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> PackedSlice<'a> {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn get(&self, index: usize) -> Option<Nucleotide> {
+        if index < self.len() {
+            self.sequence.get(self.start + index)
+        } else {
+            None
+        }
+    }
+
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> PackedSlice<'a> {
+        let inner = resolve(range, self.len());
+        PackedSlice {
+            sequence: self.sequence,
+            start: self.start + inner.start,
+            end: self.start + inner.end,
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'a> {
+        Iter {
+            sequence: self.sequence,
+            front: self.start,
+            back: self.end,
+        }
+    }
+
+    // Every run of N consecutive bases, like slice::windows
+    pub fn windows<const N: usize>(&self) -> Windows<'a, N> {
+        Windows {
+            slice: *self,
+            index: 0,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Nucleotide> {
+        self.iter().collect()
+    }
+
+    pub fn to_packed(&self) -> PackedSequence {
+        self.iter().collect()
+    }
+}
+
+impl Debug for PackedSlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text: String = self.iter().map(char::from).collect();
+        write!(f, "{}", text)
+    }
+}
+
+impl NucleotideSequence for PackedSlice<'_> {
+    fn len(&self) -> usize {
+        PackedSlice::len(self)
+    }
+
+    fn nucleotide(&self, index: usize) -> Nucleotide {
+        self.get(index).expect("index out of range")
+    }
+}
+
+pub struct Iter<'a> {
+    sequence: &'a PackedSequence,
+    front: usize,
+    back: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Nucleotide;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.sequence.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.sequence.get(self.back)
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+pub struct Windows<'a, const N: usize> {
+    slice: PackedSlice<'a>,
+    index: usize,
+}
+
+impl<const N: usize> Iterator for Windows<'_, N> {
+    type Item = [Nucleotide; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if N == 0 || self.index + N > self.slice.len() {
+            return None;
+        }
+        let mut window = [Nucleotide::A; N];
+        for (offset, nucleotide) in window.iter_mut().enumerate() {
+            *nucleotide = self.slice.nucleotide(self.index + offset);
+        }
+        self.index += 1;
+        Some(window)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NucleotideSequence, PackedSequence};
+    use crate::dna::Nucleotide;
+
+    fn bases() -> Vec<Nucleotide> {
+        vec![
+            Nucleotide::G,
+            Nucleotide::A,
+            Nucleotide::T,
+            Nucleotide::T,
+            Nucleotide::A,
+            Nucleotide::C,
+            Nucleotide::G,
+        ]
+    }
+
+    #[test]
+    fn packs_four_bases_to_a_byte() {
+        let packed = PackedSequence::from(bases());
+
+        assert_eq!(packed.len(), 7);
+        assert_eq!(packed.as_bytes().len(), 2);
+        assert_eq!(packed.to_vec(), bases());
+        assert_eq!(packed.iter().rev().collect::<Vec<_>>(), {
+            let mut reversed = bases();
+            reversed.reverse();
+            reversed
+        });
+    }
+
+    #[test]
+    fn slicing_and_windows_match_plain_vectors() {
+        let packed = PackedSequence::from(bases());
+        let plain = bases();
+
+        assert_eq!(packed.slice(2..5).to_vec(), plain[2..5].to_vec());
+        assert_eq!(packed.slice(2..).slice(1..3).to_vec(), plain[3..5].to_vec());
+        assert_eq!(
+            packed.windows::<4>().collect::<Vec<_>>(),
+            plain
+                .windows(4)
+                .map(|w| [w[0], w[1], w[2], w[3]])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(packed.slice(5..).windows::<4>().count(), 0);
+        assert_eq!(
+            packed.slice(1..).nucleotides(0..3),
+            plain[1..].nucleotides(0..3)
+        );
+    }
+
+    #[test]
+    fn editing_keeps_sequences_comparable() {
+        let mut packed = PackedSequence::from(bases());
+        let mut plain = bases();
+
+        packed.insert(3, Nucleotide::C);
+        plain.insert(3, Nucleotide::C);
+        assert_eq!(packed.to_vec(), plain);
+
+        assert_eq!(packed.remove(0), plain.remove(0));
+        packed.set(2, Nucleotide::G);
+        plain[2] = Nucleotide::G;
+        assert_eq!(packed.to_vec(), plain);
+
+        assert_eq!(packed.drain(1..4), plain.drain(1..4).collect::<Vec<_>>());
+        packed.splice(1, &[Nucleotide::T, Nucleotide::T]);
+        plain.splice(1..1, [Nucleotide::T, Nucleotide::T]);
+        assert_eq!(packed.to_vec(), plain);
+
+        // Equality is on the bytes, so leftover bits from removed bases must be cleared
+        assert_eq!(packed, PackedSequence::from(plain));
+    }
+}
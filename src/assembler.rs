@@ -0,0 +1,506 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use gene_traits::{
+    amino_acid::{AminoAcid, GeneticCode},
+    dna,
+    mutation::seeded_rng,
+    packed::PackedSequence,
+};
+use rand::{Rng, seq::SliceRandom};
+
+use crate::{
+    component_register::{ComponentRegister, HeaderTable, gene_chain},
+    components::gene_reader::{Genome, TATA_BOXES, scan_genes, transcribe},
+    config::PROMOTER_SIZE,
+    systems::translation::translate,
+};
+
+const NUCLEOTIDES: [dna::Nucleotide; 4] = [
+    dna::Nucleotide::A,
+    dna::Nucleotide::C,
+    dna::Nucleotide::T,
+    dna::Nucleotide::G,
+];
+
+// How a codon is picked when the genetic code has several for the same amino acid.  Codons that
+// would spell out a promoter are always skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodonStrategy {
+    // Always the first codon in table order
+    #[default]
+    First,
+    // Takes turns between the codons of each amino acid
+    RoundRobin,
+    // A seeded random pick, the same seed always gives the same genome
+    Random(u64),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssemblerOptions {
+    pub code: GeneticCode,
+    pub strategy: CodonStrategy,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssemblerError {
+    // Lines are counted from 1
+    InvalidLine { line: usize },
+    UnknownGene { line: usize, name: String },
+    InvalidPayload { line: usize, message: String },
+    // Every codon for the amino acid would have created a promoter
+    NoCodon { line: usize, amino_acid: AminoAcid },
+    // The genome didn't read back as written, which means the genetic code can't express the gene
+    Unreadable { line: usize },
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::InvalidLine { line } => {
+                write!(f, "expected 'Gene: payload' at line {}", line)
+            }
+            AssemblerError::UnknownGene { line, name } => {
+                write!(f, "no gene named {} is registered (line {})", name, line)
+            }
+            AssemblerError::InvalidPayload { line, message } => {
+                write!(f, "{} at line {}", message, line)
+            }
+            AssemblerError::NoCodon { line, amino_acid } => write!(
+                f,
+                "no codon for {:?} avoids creating a promoter (line {})",
+                amino_acid, line
+            ),
+            AssemblerError::Unreadable { line } => {
+                write!(f, "the gene at line {} doesn't read back as written", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+// One line of a spec, already turned into the amino acids the gene has to translate to
+#[derive(Debug)]
+pub struct GeneSpec {
+    pub line: usize,
+    pub register: &'static ComponentRegister<PROMOTER_SIZE>,
+    pub header: [AminoAcid; PROMOTER_SIZE],
+    pub payload: Vec<AminoAcid>,
+}
+
+impl GeneSpec {
+    // Header, payload and the stop, which is what translating the gene has to give back
+    pub fn chain(&self) -> Vec<AminoAcid> {
+        gene_chain(&self.header, &self.payload)
+    }
+}
+
+fn find_register(
+    name: &str,
+) -> Option<(&'static ComponentRegister<PROMOTER_SIZE>, [AminoAcid; PROMOTER_SIZE])> {
+    let name: String = name.split_whitespace().collect();
+    HeaderTable::registered()
+        .iter()
+        .find(|(c, _)| c.type_str.split_whitespace().collect::<String>() == name)
+        .map(|(c, header)| (c, *header))
+}
+
+fn encode_payload(
+    register: &ComponentRegister<PROMOTER_SIZE>,
+    body: &str,
+    line: usize,
+) -> Result<Vec<AminoAcid>, AssemblerError> {
+    (register.from_text)(body).map_err(|message| AssemblerError::InvalidPayload { line, message })
+}
+
+/**
+ * Reads a spec with one gene per line, written as the registered type name and its payload, e.g.
+ * "Activation: dopamine >= 100" or "Accumulator<Dopamine>: rate 5".  Blank lines are skipped and
+ * '#' starts a comment.
+ */
+pub fn parse_spec(spec: &str) -> Result<Vec<GeneSpec>, AssemblerError> {
+    let mut genes = Vec::new();
+    for (index, line) in spec.lines().enumerate() {
+        let line_number = index + 1;
+        let content = line.split('#').next().unwrap_or("").trim();
+        if content.is_empty() {
+            continue;
+        }
+        let (name, body) = content
+            .split_once(':')
+            .ok_or(AssemblerError::InvalidLine { line: line_number })?;
+        let (register, header) = find_register(name).ok_or_else(|| AssemblerError::UnknownGene {
+            line: line_number,
+            name: name.trim().to_string(),
+        })?;
+        genes.push(GeneSpec {
+            line: line_number,
+            register,
+            header,
+            payload: encode_payload(register, body.trim(), line_number)?,
+        });
+    }
+    Ok(genes)
+}
+
+// Puts candidate codons in the order the strategy prefers them
+struct CodonPicker<R: Rng> {
+    code: GeneticCode,
+    strategy: CodonStrategy,
+    rng: R,
+    turns: HashMap<AminoAcid, usize>,
+}
+
+impl<R: Rng> CodonPicker<R> {
+    fn candidates(&mut self, amino_acid: AminoAcid) -> Vec<[dna::Nucleotide; 3]> {
+        let mut codons: Vec<_> = self
+            .code
+            .codons_for(amino_acid)
+            .into_iter()
+            .map(|codon| codon.map(dna::Nucleotide::from))
+            .collect();
+        match self.strategy {
+            CodonStrategy::First => {}
+            CodonStrategy::RoundRobin => {
+                let turn = self.turns.entry(amino_acid).or_default();
+                if !codons.is_empty() {
+                    let shift = *turn % codons.len();
+                    codons.rotate_left(shift);
+                }
+                *turn += 1;
+            }
+            CodonStrategy::Random(_) => codons.shuffle(&mut self.rng),
+        }
+        codons
+    }
+}
+
+// The start of every promoter that appending the bases would add to the sequence
+fn new_promoters(sequence: &[dna::Nucleotide], bases: &[dna::Nucleotide]) -> Vec<usize> {
+    let from = sequence.len().saturating_sub(3);
+    let mut joined = sequence[from..].to_vec();
+    joined.extend_from_slice(bases);
+    joined
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| TATA_BOXES.iter().any(|b| b == window))
+        .map(|(index, _)| from + index)
+        .collect()
+}
+
+struct GenomeWriter<R: Rng> {
+    picker: CodonPicker<R>,
+    sequence: Vec<dna::Nucleotide>,
+}
+
+impl<R: Rng> GenomeWriter<R> {
+    /**
+     * Writes the promoter, a spacer of up to two bases and the start codon.  The promoter tail and
+     * the spacer are transcribed too, so they must not hold a start codon of their own, which
+     * would shift the reading frame.
+     */
+    fn open_gene(&mut self, line: usize) -> Result<(), AssemblerError> {
+        let spacers = std::iter::once(vec![])
+            .chain(NUCLEOTIDES.iter().map(|&n| vec![n]))
+            .chain(
+                NUCLEOTIDES
+                    .iter()
+                    .flat_map(|&a| NUCLEOTIDES.iter().map(move |&b| vec![a, b])),
+            );
+        let starts = self.picker.candidates(AminoAcid::START);
+
+        for spacer in spacers {
+            for promoter in TATA_BOXES {
+                for start in &starts {
+                    let mut opening = promoter.to_vec();
+                    opening.extend_from_slice(&spacer);
+                    opening.extend_from_slice(start);
+
+                    if new_promoters(&self.sequence, &opening) != [self.sequence.len()] {
+                        continue;
+                    }
+                    // Everything after the first base of the promoter gets transcribed
+                    let transcribed: Vec<_> = opening[1..].iter().map(|&n| n.into()).collect();
+                    let first_start = transcribed.windows(3).position(|codon| {
+                        self.picker.code.translate([codon[0], codon[1], codon[2]])
+                            == AminoAcid::START
+                    });
+                    if first_start == Some(transcribed.len() - 3) {
+                        self.sequence.extend_from_slice(&opening);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Err(AssemblerError::NoCodon {
+            line,
+            amino_acid: AminoAcid::START,
+        })
+    }
+
+    /**
+     * Writes a codon for every amino acid.  A codon can only form a promoter together with the
+     * one before it, so when an amino acid has no codon left that fits, the previous amino acid
+     * gets its next codon and the search carries on from there.
+     */
+    fn push_chain(&mut self, chain: &[AminoAcid], line: usize) -> Result<(), AssemblerError> {
+        let candidates: Vec<_> = chain
+            .iter()
+            .map(|&amino_acid| self.picker.candidates(amino_acid))
+            .collect();
+        let no_codon = |position: usize| AssemblerError::NoCodon {
+            line,
+            amino_acid: chain[position],
+        };
+        if let Some(position) = candidates.iter().position(|codons| codons.is_empty()) {
+            return Err(no_codon(position));
+        }
+
+        let base = self.sequence.len();
+        // The next candidate to try for every position
+        let mut next = vec![0; chain.len()];
+        let mut position = 0;
+        let mut deepest = 0;
+        while position < chain.len() {
+            self.sequence.truncate(base + position * 3);
+            let found = candidates[position]
+                .iter()
+                .enumerate()
+                .skip(next[position])
+                .find(|(_, codon)| new_promoters(&self.sequence, &codon[..]).is_empty());
+            match found {
+                Some((index, codon)) => {
+                    self.sequence.extend_from_slice(codon);
+                    next[position] = index + 1;
+                    position += 1;
+                    deepest = deepest.max(position);
+                }
+                // Going back further than one amino acid can't change anything
+                None if position == 0 || position + 1 < deepest => {
+                    self.sequence.truncate(base);
+                    return Err(no_codon(deepest.min(chain.len() - 1)));
+                }
+                None => {
+                    next[position] = 0;
+                    position -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The scanner never transcribes the last 3 bases, so the final gene needs some padding
+    fn pad(&mut self) {
+        for _ in 0..3 {
+            let base = NUCLEOTIDES
+                .into_iter()
+                .find(|&n| new_promoters(&self.sequence, &[n]).is_empty())
+                .expect("Only one base can complete a promoter");
+            self.sequence.push(base);
+        }
+    }
+}
+
+/**
+ * Writes the genes into a genome, one after the other.  Each gene gets a TATA box promoter, a
+ * start codon, the header registered for its type, the payload and a stop codon.  The genome is
+ * read back before it is returned, so a genome that comes out of here grows the genes it was
+ * given.
+ */
+pub fn assemble_genes(
+    genes: &[GeneSpec],
+    options: &AssemblerOptions,
+) -> Result<Genome, AssemblerError> {
+    let seed = match options.strategy {
+        CodonStrategy::Random(seed) => seed,
+        _ => 0,
+    };
+    let mut writer = GenomeWriter {
+        picker: CodonPicker {
+            code: options.code,
+            strategy: options.strategy,
+            rng: seeded_rng(seed),
+            turns: HashMap::new(),
+        },
+        sequence: Vec::new(),
+    };
+
+    for gene in genes {
+        writer.open_gene(gene.line)?;
+        writer.push_chain(&gene.chain(), gene.line)?;
+    }
+    if !genes.is_empty() {
+        writer.pad();
+    }
+
+    let sequence = PackedSequence::from(writer.sequence);
+    let mut read_back = scan_genes(&sequence)
+        .map(|location| translate(&transcribe(&sequence, &location), &options.code));
+    for gene in genes {
+        if read_back.next() != Some(gene.chain()) {
+            return Err(AssemblerError::Unreadable { line: gene.line });
+        }
+    }
+
+    Ok(Genome { sequence })
+}
+
+pub fn assemble(spec: &str, options: &AssemblerOptions) -> Result<Genome, AssemblerError> {
+    assemble_genes(&parse_spec(spec)?, options)
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, Update},
+        ecs::schedule::IntoScheduleConfigs,
+    };
+    use gene_traits::{
+        amino_acid::{AminoAcid, GeneticCode},
+        rna,
+    };
+
+    use super::{AssemblerError, AssemblerOptions, CodonStrategy, assemble, parse_spec};
+    use crate::{
+        components::{
+            Activation, Dopamine, Serotonin,
+            accumulator::Accumulator,
+            expr_variables::ExprVariables,
+            gene_reader::{AttachedGenome, GeneParser, Genome, scan_genes},
+        },
+        systems::{ribosome::parse_amino_acid_strand, translation::translate_rna_strand},
+    };
+
+    const SPEC: &str = "\
+# A neuron that fires once it has built up enough dopamine
+Activation: dopamine >= 100
+Accumulator<Dopamine>: rate 5
+Accumulator<Serotonin>: rate 42
+";
+
+    fn grow(genome: Genome) -> App {
+        let mut app = App::new();
+        app.add_systems(
+            Update,
+            (
+                crate::components::gene_reader::parse_attached_genome,
+                translate_rna_strand,
+                parse_amino_acid_strand,
+            )
+                .chain(),
+        );
+        let genome_entity = app.world_mut().spawn(genome).id();
+        app.world_mut().spawn((
+            GeneParser {},
+            AttachedGenome {
+                genome_entity,
+                start_index: 0,
+            },
+        ));
+        app.update();
+        app
+    }
+
+    #[test]
+    fn assembled_genome_grows_the_spec() {
+        let genome = assemble(SPEC, &AssemblerOptions::default()).unwrap();
+        assert_eq!(scan_genes(&genome.sequence).count(), 3);
+
+        let mut app = grow(genome);
+        let world = app.world_mut();
+        let mut query =
+            world.query::<(&Activation, &Accumulator<Dopamine>, &Accumulator<Serotonin>)>();
+        let (activation, dopamine, serotonin) = query.single(world).unwrap();
+
+        assert_eq!(dopamine.buildup_rate, 5);
+        assert_eq!(serotonin.buildup_rate, 42);
+        assert!(activation.get_activation(&ExprVariables::default().with_level::<Dopamine>(100)));
+        assert!(!activation.get_activation(&ExprVariables::default().with_level::<Dopamine>(99)));
+    }
+
+    #[test]
+    fn strategies_pick_different_codons() {
+        let first = assemble(SPEC, &AssemblerOptions::default()).unwrap();
+        let round_robin = assemble(
+            SPEC,
+            &AssemblerOptions {
+                strategy: CodonStrategy::RoundRobin,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let random = |seed| {
+            assemble(
+                SPEC,
+                &AssemblerOptions {
+                    strategy: CodonStrategy::Random(seed),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .sequence
+        };
+
+        assert_ne!(first.sequence, round_robin.sequence);
+        assert_eq!(random(3), random(3));
+        assert_ne!(random(3), random(4));
+    }
+
+    #[test]
+    fn assembles_with_another_genetic_code() {
+        // Move valine to a codon of its own, the genome has to use it to read back correctly
+        let mut code = GeneticCode::STANDARD;
+        for codon in code.codons_for(AminoAcid::V) {
+            code.set(codon, AminoAcid::Y);
+        }
+        code.set(
+            [rna::Nucleotide::G, rna::Nucleotide::C, rna::Nucleotide::A],
+            AminoAcid::V,
+        );
+        let options = AssemblerOptions {
+            code,
+            ..Default::default()
+        };
+        assert!(assemble("Accumulator<Dopamine>: rate 19", &options).is_ok());
+
+        // The wobble code is missing amino acids that every expression needs
+        let options = AssemblerOptions {
+            code: GeneticCode::WOBBLE,
+            ..Default::default()
+        };
+        assert!(matches!(
+            assemble("Activation: dopamine >= 100", &options),
+            Err(AssemblerError::NoCodon { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn reports_bad_specs() {
+        assert_eq!(
+            parse_spec("Activation dopamine").unwrap_err(),
+            AssemblerError::InvalidLine { line: 1 }
+        );
+        assert_eq!(
+            parse_spec("\nAccumulator<Unknown>: rate 1").unwrap_err(),
+            AssemblerError::UnknownGene {
+                line: 2,
+                name: "Accumulator<Unknown>".to_string()
+            }
+        );
+        assert!(matches!(
+            parse_spec("Accumulator<Dopamine>: rate fast"),
+            Err(AssemblerError::InvalidPayload { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_spec("Activation: dopamine = 1"),
+            Err(AssemblerError::InvalidPayload { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_spec("Activation: (dopamine"),
+            Err(AssemblerError::InvalidPayload { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use std::{error::Error, io, path::Path};
+
+use articial_life::{
+    assembler::{AssemblerOptions, CodonStrategy, assemble},
+    components::gene_reader::Genome,
+    disassembler::disassemble,
+    fasta::{FastaHeader, FastaRecord, read_fasta_file, write_fasta},
+};
+
+use crate::genetic_code;
+
+type CommandResult = Result<(), Box<dyn Error>>;
+
+// Runs a command given on the command line, or hands back None when the simulation should start
+pub fn run(args: &[String]) -> Option<CommandResult> {
+    match args.first().map(String::as_str) {
+        Some("assemble") => Some(assemble_command(&args[1..])),
+        Some("disassemble") => Some(disassemble_command(&args[1..])),
+        _ => None,
+    }
+}
+
+fn parse_strategy(text: &str) -> Result<CodonStrategy, Box<dyn Error>> {
+    match text.split_once('=') {
+        None if text == "first" => Ok(CodonStrategy::First),
+        None if text == "round-robin" => Ok(CodonStrategy::RoundRobin),
+        Some(("random", seed)) => Ok(CodonStrategy::Random(seed.parse()?)),
+        _ => Err(format!(
+            "unknown codon strategy {:?}, expected first, round-robin or random=<seed>",
+            text
+        )
+        .into()),
+    }
+}
+
+/**
+ * assemble <spec> [--strategy first|round-robin|random=<seed>]
+ * Compiles the spec into a genome and writes it to stdout as FASTA, named after the spec file.
+ */
+fn assemble_command(args: &[String]) -> CommandResult {
+    let mut spec_path = None;
+    let mut strategy = CodonStrategy::First;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => {
+                strategy = parse_strategy(args.next().ok_or("--strategy needs a value")?)?
+            }
+            path if spec_path.is_none() => spec_path = Some(path),
+            other => return Err(format!("unexpected argument {:?}", other).into()),
+        }
+    }
+    let spec_path = spec_path.ok_or("usage: assemble <spec> [--strategy <strategy>]")?;
+
+    let options = AssemblerOptions {
+        code: genetic_code(),
+        strategy,
+    };
+    let genome = assemble(&std::fs::read_to_string(spec_path)?, &options)?;
+
+    let id = Path::new(spec_path)
+        .file_stem()
+        .map_or("genome".into(), |stem| stem.to_string_lossy());
+    let record = FastaRecord {
+        header: FastaHeader {
+            id: id.into_owned(),
+            metadata: vec![("strategy".to_string(), format!("{:?}", strategy))],
+            description: String::new(),
+        },
+        sequence: genome,
+    };
+    write_fasta(io::stdout().lock(), &[record])?;
+    Ok(())
+}
+
+/**
+ * disassemble <fasta>
+ * Prints every gene of every genome in the file, with the headers it matched and the decoded
+ * payloads.
+ */
+fn disassemble_command(args: &[String]) -> CommandResult {
+    let [path] = args else {
+        return Err("usage: disassemble <fasta>".into());
+    };
+    let code = genetic_code();
+    for record in read_fasta_file::<Genome>(path)? {
+        println!(">{}", record.header);
+        for gene in disassemble(&record.sequence, &code) {
+            print!("{}", gene);
+        }
+    }
+    Ok(())
+}
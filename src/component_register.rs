@@ -1,20 +1,532 @@
-use bevy::ecs::system::EntityCommands;
-use gene_traits::amino_acid::AminoAcid;
-use inventory;
+use std::{
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+use bevy::ecs::{component::Component, system::EntityCommands, world::EntityRef};
+use gene_traits::{
+    amino_acid::AminoAcid,
+    header::{Gene, GeneId, HeaderAssignmentError, HeaderRequest, assign_headers},
+};
+
+use crate::config::{HEADER_MIN_DISTANCE, PROMOTER_SIZE};
+
+// Writes the payload of the component the entity carries, or says why it can't
+pub type EncoderFn = fn(&EntityRef) -> Option<Result<Vec<AminoAcid>, String>>;
+
+// Turns the text of a spec line into a payload, or says why it can't
+pub type FromTextFn = fn(&str) -> Result<Vec<AminoAcid>, String>;
+
+// Writes a payload the way a spec line would spell it
+pub type ToTextFn = fn(&[AminoAcid]) -> String;
 
 /**
- * ComponentRegister registers the promoter sequence, a descriptive type name,
- * and a parsing function that takes the AminoAcid sequence and outputs the number of Amino Acids consumed during parsing
+ * ComponentRegister registers the gene id and salt the header is derived from, a descriptive type name,
+ * a parsing function that takes the AminoAcid sequence and outputs the number of Amino Acids consumed during parsing,
+ * an encoder that goes the other way, and the text hooks the assembler and disassembler write payloads with.
+ * N is the length of the header the gene is read with, the header itself is handed out by HeaderTable.
  */
 #[derive(Debug)]
 pub struct ComponentRegister<const N: usize> {
-    pub header: [AminoAcid; N],
-    pub type_hash: u128,
+    pub id: GeneId,
+    /**
+     * The candidate header the gene is pinned to, see assign_headers.  Unpinned genes take the
+     * first candidate that is far enough from the others, which can change as genes are added.
+     */
+    pub salt: Option<u32>,
     pub type_str: &'static str,
     /**
-     * Parser must return the number of amino acids consumed so that a long multi-gene strand can be processed
+     * Parser must return the number of amino acids consumed so that a long multi-gene strand can be processed,
+     * a gene that doesn't build a component still says how much of the strand it took up
+     */
+    pub parser: fn(&[AminoAcid], EntityCommands) -> Result<usize, GeneParseError>,
+    /**
+     * Encoder writes the payload of the component the entity carries, None when it doesn't carry one
+     */
+    pub encoder: EncoderFn,
+    /**
+     * Payloads as the assembler and disassembler write them, without the header and the stop
      */
-    pub parser: fn(&[AminoAcid], EntityCommands) -> usize,
+    pub from_text: FromTextFn,
+    pub to_text: ToTextFn,
+}
+
+inventory::collect!(ComponentRegister<PROMOTER_SIZE>);
+
+// A gene that was read but couldn't be turned into a component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneParseError {
+    pub consumed: usize,
+    pub message: String,
+}
+
+impl Display for GeneParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (after {} amino acids)", self.message, self.consumed)
+    }
+}
+
+impl std::error::Error for GeneParseError {}
+
+/**
+ * The way back from a component to the payload of the gene that builds it, without the header and
+ * the stop.  Parsing the payload has to give back an equivalent component, the error explains what
+ * about the component no gene can express.
+ */
+pub trait GeneEncoder {
+    fn encode_payload(&self) -> Result<Vec<AminoAcid>, String>;
+
+    // The payload a spec line spells, e.g. "rate 5" for an accumulator
+    fn payload_from_text(text: &str) -> Result<Vec<AminoAcid>, String>;
+
+    // The other way, payload_from_text has to read the text back to an equivalent payload
+    fn payload_to_text(payload: &[AminoAcid]) -> String;
+}
+
+// The encoder the Gene derive registers for T
+pub fn encode_component<T: Component + GeneEncoder>(
+    entity: &EntityRef,
+) -> Option<Result<Vec<AminoAcid>, String>> {
+    entity.get::<T>().map(T::encode_payload)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeError {
+    pub gene: &'static str,
+    pub message: String,
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't encode {}: {}", self.gene, self.message)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+// Header, payload and stop, the chain that builds the component again
+pub fn encode_gene<T: Gene + GeneEncoder>(component: &T) -> Result<Vec<AminoAcid>, EncodeError> {
+    let header = HeaderTable::registered().header_of(T::ID);
+    let error = |message: String| EncodeError {
+        gene: T::ID.name,
+        message,
+    };
+    let header = header.ok_or_else(|| error("not a registered gene".to_string()))?;
+    let payload = component.encode_payload().map_err(error)?;
+    Ok(gene_chain(&header, &payload))
+}
+
+pub fn gene_chain(header: &[AminoAcid], payload: &[AminoAcid]) -> Vec<AminoAcid> {
+    let mut chain = header.to_vec();
+    chain.extend_from_slice(payload);
+    chain.push(AminoAcid::STOP);
+    chain
+}
+
+/**
+ * The header every gene is read with.  Headers are assigned over the whole set of registered
+ * genes at once, so that any two of them are at least a minimum number of edits apart.
+ * Assigned tables are sorted by id hash, which keeps them in the same order on every run.
+ */
+#[derive(Debug)]
+pub struct HeaderTable<const N: usize> {
+    entries: Vec<(&'static ComponentRegister<N>, [AminoAcid; N])>,
+}
+
+impl HeaderTable<PROMOTER_SIZE> {
+    // The table for every registered gene, assigned the first time it is asked for
+    pub fn registered() -> &'static Self {
+        static TABLE: OnceLock<HeaderTable<PROMOTER_SIZE>> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            Self::assign(
+                inventory::iter::<ComponentRegister<PROMOTER_SIZE>>,
+                HEADER_MIN_DISTANCE,
+            )
+            .unwrap_or_else(|e| panic!("Couldn't assign gene headers: {}", e))
+        })
+    }
+}
+
+impl<const N: usize> HeaderTable<N> {
+    // A table with headers chosen by hand, nothing checks how far apart they are
+    pub fn new(entries: Vec<(&'static ComponentRegister<N>, [AminoAcid; N])>) -> Self {
+        Self { entries }
+    }
+
+    pub fn assign(
+        registers: impl IntoIterator<Item = &'static ComponentRegister<N>>,
+        min_distance: usize,
+    ) -> Result<Self, HeaderAssignmentError> {
+        let mut registers: Vec<_> = registers.into_iter().collect();
+        registers.sort_by_key(|register| register.id.hash());
+        let requests: Vec<HeaderRequest> = registers
+            .iter()
+            .map(|r| HeaderRequest {
+                id: r.id,
+                salt: r.salt,
+            })
+            .collect();
+        let headers = assign_headers::<N>(&requests, min_distance)?;
+        Ok(Self::new(
+            registers
+                .into_iter()
+                .zip(headers.into_iter().map(|assigned| assigned.header))
+                .collect(),
+        ))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static ComponentRegister<N>, &[AminoAcid; N])> {
+        self.entries
+            .iter()
+            .map(|(register, header)| (*register, header))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn header_of(&self, id: GeneId) -> Option<[AminoAcid; N]> {
+        self.iter()
+            .find(|(register, _)| register.id == id)
+            .map(|(_, header)| *header)
+    }
+
+    // The chains of every gene in the table the entity carries, in table order
+    pub fn encode(&self, entity: &EntityRef) -> Result<Vec<Vec<AminoAcid>>, EncodeError> {
+        self.iter()
+            .filter_map(|(register, header)| {
+                let payload = (register.encoder)(entity)?;
+                Some(
+                    payload
+                        .map(|payload| gene_chain(header, &payload))
+                        .map_err(|message| EncodeError {
+                            gene: register.type_str,
+                            message,
+                        }),
+                )
+            })
+            .collect()
+    }
+}
+
+// Largest distance at which a single window of a chain can be accepted as either of two headers.
+// The ribosome accepts headers less than N / 2 edits away, so two headers this close or closer can
+// shadow each other.
+pub const fn ambiguity_distance(header_length: usize) -> usize {
+    2 * (header_length / 2).saturating_sub(1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    // The headers are identical, the ribosome can never tell the types apart
+    Collision,
+    // Some windows are close enough to both headers to be read as either
+    NearCollision,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderConflict {
+    pub first: &'static str,
+    pub second: &'static str,
+    pub distance: usize,
+    pub kind: ConflictKind,
+}
+
+/**
+ * The pairwise Levenshtein distances between the headers of a table, in table order.
+ * Displaying it prints the matrix followed by every conflict.
+ */
+#[derive(Debug)]
+pub struct HeaderReport<const N: usize> {
+    pub registers: Vec<&'static ComponentRegister<N>>,
+    pub headers: Vec<[AminoAcid; N]>,
+    pub distances: Vec<Vec<usize>>,
+}
+
+impl HeaderReport<PROMOTER_SIZE> {
+    pub fn registered() -> Self {
+        Self::new(HeaderTable::registered())
+    }
+}
+
+impl<const N: usize> HeaderReport<N> {
+    pub fn new(table: &HeaderTable<N>) -> Self {
+        let (registers, headers): (Vec<_>, Vec<_>) = table
+            .iter()
+            .map(|(register, header)| (register, *header))
+            .unzip();
+        let distances = headers
+            .iter()
+            .map(|a| {
+                headers
+                    .iter()
+                    .map(|b| generic_levenshtein::distance(a, b))
+                    .collect()
+            })
+            .collect();
+        Self {
+            registers,
+            headers,
+            distances,
+        }
+    }
+
+    pub fn conflicts(&self) -> Vec<HeaderConflict> {
+        let mut conflicts = Vec::new();
+        for (i, first) in self.registers.iter().enumerate() {
+            for (j, second) in self.registers.iter().enumerate().skip(i + 1) {
+                let distance = self.distances[i][j];
+                let kind = if distance == 0 {
+                    ConflictKind::Collision
+                } else if distance <= ambiguity_distance(N) {
+                    ConflictKind::NearCollision
+                } else {
+                    continue;
+                };
+                conflicts.push(HeaderConflict {
+                    first: first.type_str,
+                    second: second.type_str,
+                    distance,
+                    kind,
+                });
+            }
+        }
+        conflicts
+    }
+
+    // Ok only when no two headers are identical, near collisions are left to the caller
+    pub fn validate(&self) -> Result<(), Vec<HeaderConflict>> {
+        let collisions: Vec<_> = self
+            .conflicts()
+            .into_iter()
+            .filter(|conflict| conflict.kind == ConflictKind::Collision)
+            .collect();
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(collisions)
+        }
+    }
 }
 
-inventory::collect!(ComponentRegister<4>);
+impl<const N: usize> Display for HeaderReport<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .registers
+            .iter()
+            .map(|r| r.type_str.len())
+            .max()
+            .unwrap_or(0);
+        write!(f, "{:width$}", "", width = width + N + 4)?;
+        for index in 0..self.registers.len() {
+            write!(f, " {:>2}", index)?;
+        }
+        writeln!(f)?;
+        for (index, register) in self.registers.iter().enumerate() {
+            let header: String = self.headers[index].iter().map(|&a| char::from(a)).collect();
+            write!(f, "{:>2} {:<width$} {}", index, register.type_str, header)?;
+            for distance in &self.distances[index] {
+                write!(f, " {:>2}", distance)?;
+            }
+            writeln!(f)?;
+        }
+        for conflict in self.conflicts() {
+            writeln!(
+                f,
+                "{:?}: {} and {} are {} edits apart",
+                conflict.kind, conflict.first, conflict.second, conflict.distance
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, Update},
+        ecs::{hierarchy::ChildOf, system::EntityCommands, world::EntityRef},
+    };
+    use gene_traits::{
+        amino_acid::AminoAcid,
+        header::{GeneId, MAX_ATTEMPTS, candidate_header},
+    };
+
+    use crate::{
+        assembler::parse_spec,
+        components::amino_acid_reader::{AminoAcidChain, AminoAcidReader},
+        systems::ribosome::parse_amino_acid_strand,
+    };
+
+    use super::{
+        ComponentRegister, ConflictKind, GeneParseError, HeaderReport, HeaderTable,
+        ambiguity_distance,
+    };
+    use crate::config::{HEADER_MIN_DISTANCE, PROMOTER_SIZE};
+
+    fn ignore_gene(_: &[AminoAcid], _: EntityCommands) -> Result<usize, GeneParseError> {
+        Ok(0)
+    }
+
+    fn encode_nothing(_: &EntityRef) -> Option<Result<Vec<AminoAcid>, String>> {
+        None
+    }
+
+    fn no_text(_: &str) -> Result<Vec<AminoAcid>, String> {
+        Err("no text".to_string())
+    }
+
+    fn no_payload(_: &[AminoAcid]) -> String {
+        String::new()
+    }
+
+    const fn register(type_str: &'static str, version: u32) -> ComponentRegister<4> {
+        ComponentRegister {
+            id: GeneId::new("test", version),
+            salt: None,
+            type_str,
+            parser: ignore_gene,
+            encoder: encode_nothing,
+            from_text: no_text,
+            to_text: no_payload,
+        }
+    }
+
+    static REGISTERS: [ComponentRegister<4>; 4] = [
+        register("First", 1),
+        register("Same", 2),
+        register("Close", 3),
+        register("Far", 4),
+    ];
+
+    fn hand_picked() -> HeaderTable<4> {
+        HeaderTable::new(vec![
+            (
+                &REGISTERS[0],
+                [AminoAcid::A, AminoAcid::R, AminoAcid::N, AminoAcid::D],
+            ),
+            (
+                &REGISTERS[1],
+                [AminoAcid::A, AminoAcid::R, AminoAcid::N, AminoAcid::D],
+            ),
+            (
+                &REGISTERS[2],
+                [AminoAcid::A, AminoAcid::R, AminoAcid::W, AminoAcid::W],
+            ),
+            (
+                &REGISTERS[3],
+                [AminoAcid::W, AminoAcid::W, AminoAcid::Y, AminoAcid::Y],
+            ),
+        ])
+    }
+
+    #[test]
+    fn registered_headers_do_not_collide() {
+        let report = HeaderReport::registered();
+        assert!(
+            report.conflicts().is_empty(),
+            "Registered gene headers are ambiguous:\n{}",
+            report
+        );
+        for (i, row) in report.distances.iter().enumerate() {
+            for (j, distance) in row.iter().enumerate() {
+                assert!(i == j || *distance >= HEADER_MIN_DISTANCE);
+            }
+        }
+        assert!(HEADER_MIN_DISTANCE > ambiguity_distance(PROMOTER_SIZE));
+    }
+
+    // Unpinned genes can move when other genes are registered, which would make saved genomes
+    // unreadable
+    #[test]
+    fn registered_headers_are_pinned() {
+        for (register, header) in HeaderTable::registered().iter() {
+            let salt = (0..MAX_ATTEMPTS)
+                .find(|&salt| candidate_header(register.id.hash(), salt) == *header)
+                .expect("Assigned headers are candidates");
+            assert_eq!(
+                register.salt,
+                Some(salt),
+                "pin {} with salt = {} in its #[gene(...)]",
+                register.id,
+                salt
+            );
+        }
+    }
+
+    #[test]
+    fn reports_collisions_and_near_collisions() {
+        let report = HeaderReport::new(&hand_picked());
+        let conflicts: Vec<_> = report
+            .conflicts()
+            .into_iter()
+            .map(|c| (c.first, c.second, c.distance, c.kind))
+            .collect();
+
+        assert_eq!(report.distances[0], vec![0, 0, 2, 4]);
+        assert_eq!(
+            conflicts,
+            vec![
+                ("First", "Same", 0, ConflictKind::Collision),
+                ("First", "Close", 2, ConflictKind::NearCollision),
+                ("Same", "Close", 2, ConflictKind::NearCollision),
+            ]
+        );
+        assert_eq!(report.validate().unwrap_err().len(), 1);
+
+        let printed = report.to_string();
+        assert!(printed.contains("Collision: First and Same are 0 edits apart"));
+    }
+
+    // Runs the chains through the ribosome into one organism and encodes it again
+    fn grow_and_encode(chains: Vec<Vec<AminoAcid>>) -> Vec<Vec<AminoAcid>> {
+        let mut app = App::new();
+        app.add_systems(Update, parse_amino_acid_strand);
+        let organism = app.world_mut().spawn_empty().id();
+        for chain in chains {
+            app.world_mut()
+                .spawn((AminoAcidReader, AminoAcidChain(chain), ChildOf(organism)));
+        }
+        app.update();
+
+        HeaderTable::registered()
+            .encode(&app.world().entity(organism))
+            .unwrap()
+    }
+
+    #[test]
+    fn every_registered_gene_round_trips() {
+        let spec = "Activation: (dopamine + 3) > (serotonin * 2)
+            UpdateFunction: norepinephrine = norepinephrine - 1; dopamine = 0
+            Accumulator<Dopamine>: rate 40
+            Accumulator<Serotonin>: rate 0
+            Accumulator<Norepinephrine>: rate 19
+            Clearance<Dopamine>: resting 0 decay 50 reuptake_max 20 reuptake_half 100 degradation 1
+            Clearance<Serotonin>: resting 10 decay 0 reuptake_max 399 reuptake_half 1 degradation 0
+            Clearance<Norepinephrine>: resting 0 decay 0 reuptake_max 0 reuptake_half 0 degradation 0";
+        let chains: Vec<_> = parse_spec(spec)
+            .unwrap()
+            .iter()
+            .map(|gene| gene.chain())
+            .collect();
+
+        let encoded = grow_and_encode(chains.clone());
+        assert_eq!(encoded.len(), HeaderTable::registered().len());
+        // What the spec wrote is already canonical, only the order of the genes can differ
+        for chain in &chains {
+            assert!(encoded.contains(chain));
+        }
+        assert_eq!(grow_and_encode(encoded.clone()), encoded);
+    }
+
+    #[test]
+    fn ambiguity_grows_with_header_length() {
+        assert_eq!(ambiguity_distance(2), 0);
+        assert_eq!(ambiguity_distance(4), 2);
+        assert_eq!(ambiguity_distance(8), 6);
+    }
+}
@@ -1,14 +1,11 @@
 use bevy::ecs::system::EntityCommands;
 use bevy::prelude::Component;
 use gene_traits::amino_acid::AminoAcid;
-use gene_traits::{dna::get_header, mul, register_gene};
-use hashed_type_def::HashedTypeDef;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
-use crate::ComponentRegister;
-
-use crate::neurotransmitters::*;
+use crate::component_register::{GeneEncoder, GeneParseError};
+use crate::components::expr_gene::{consumed_through_stop, last_idx_before_stop};
 
 fn accumulator_sequence_parser<T>(gene: &[AminoAcid]) -> (Accumulator<T>, usize)
 where
@@ -17,60 +14,33 @@ where
     T: Debug,
     T: 'static,
 {
-    let mut buildup_rate = 0;
-    // The length is somehow going to have to be known.  Will have to do a pre-read until the end of the gene is encountered.
-    let mut last_index = 0;
-    let mut slice_window: [AminoAcid; 4] = [AminoAcid::A; 4];
-    let gene_end: [AminoAcid; 4] = [AminoAcid::UNKNOWN; 4];
-
-    let mut gene_iter = gene.iter();
-    let mut consumed = gene.len();
-    let mut get_last = || {
-        while let Some(acid) = gene_iter.next() {
-            if last_index < 3 {
-                slice_window[last_index] = *acid;
-            } else {
-                slice_window[0] = slice_window[1];
-                slice_window[1] = slice_window[2];
-                slice_window[2] = slice_window[3];
-                slice_window[3] = *acid;
-            }
-
-            if gene_end == slice_window {
-                consumed = last_index + 1;
-                last_index = last_index - 3;
-                return last_index;
-            }
-            last_index += 1;
-        }
-        return last_index;
-    };
+    let last_index = last_idx_before_stop(gene);
+    let consumed = consumed_through_stop(gene, last_index);
 
-    let gene_ref = &gene[0..get_last()];
-    for i in 0..gene_ref.len() {
-        let current_part: u32 = Into::<u8>::into(gene[i]) as u32;
-
-        buildup_rate += current_part; // Since each amino acid can represent a value from 0-19, this seemed the simplest way to approach this
-    }
-
-    (Accumulator::new(0, buildup_rate), consumed)
+    (Accumulator::new(0, rate_from_payload(&gene[..last_index])), consumed)
 }
 
-fn accumulator_parser<T>(gene: &[AminoAcid], mut commands: EntityCommands) -> usize
-where
-    T: Send,
-    T: Sync,
-    T: Debug,
-    T: 'static,
-{
-    let (accumulator, consumed) = accumulator_sequence_parser::<T>(gene);
-    println!("Adding in an accumulator: {:?}", accumulator);
+// Since each amino acid can represent a value from 0-19, this seemed the simplest way to approach this.
+// Unknown codons and stray start codons have no value and are skipped.
+fn rate_from_payload(payload: &[AminoAcid]) -> u32 {
+    payload
+        .iter()
+        .filter_map(|acid| u8::try_from(*acid).ok())
+        .map(u32::from)
+        .sum()
+}
 
-    commands.insert(accumulator);
-    consumed
+// The parser sums the values, so the rate is spelled with as many 19s as fit and the remainder
+pub fn rate_payload(rate: u32) -> Vec<AminoAcid> {
+    let mut acids = vec![AminoAcid::V; (rate / 19) as usize];
+    if !rate.is_multiple_of(19) {
+        acids.push(AminoAcid::try_from((rate % 19) as u8).expect("Below 19"));
+    }
+    acids
 }
 
-#[derive(Component, Default, Debug, HashedTypeDef)]
+// Registered as a gene once per neurotransmitter by neurotransmitters!
+#[derive(Component, Default, Debug)]
 pub struct Accumulator<T>
 where
     T: Send,
@@ -92,51 +62,62 @@ where
         Self {
             level,
             buildup_rate,
-            _phantom: PhantomData::default(),
+            _phantom: PhantomData,
         }
     }
+
+    // Every payload spells some rate, so accumulator genes never fail
+    pub fn sequence_parser(
+        gene: &[AminoAcid],
+        mut commands: EntityCommands,
+    ) -> Result<usize, GeneParseError>
+    where
+        T: 'static,
+    {
+        let (accumulator, consumed) = accumulator_sequence_parser::<T>(gene);
+        commands.insert(accumulator);
+        Ok(consumed)
+    }
 }
 
-#[derive(HashedTypeDef)]
-#[allow(dead_code)]
-struct DopamineAccumulator(Accumulator<Dopamine>);
-
-register_gene!(
-    Accumulator<Dopamine>,
-    { DopamineAccumulator::TYPE_HASH_NATIVE },
-    accumulator_parser<Dopamine>,
-    { crate::config::PROMOTER_SIZE }
-);
-
-#[derive(HashedTypeDef)]
-#[allow(dead_code)]
-struct SerotoninAccumulator(Accumulator<Serotonin>);
-
-register_gene!(
-    Accumulator<Seratonin>,
-    { SerotoninAccumulator::TYPE_HASH_NATIVE },
-    accumulator_parser<Serotonin>,
-    { crate::config::PROMOTER_SIZE }
-);
-
-#[derive(HashedTypeDef)]
-#[allow(dead_code)]
-struct NorepinephrineAccumulator(Accumulator<Norepinephrine>);
-
-register_gene!(
-    Accumulator<Norepinephrine>,
-    { NorepinephrineAccumulator::TYPE_HASH_NATIVE },
-    accumulator_parser<Norepinephrine>,
-    { crate::config::PROMOTER_SIZE }
-);
+// Only the buildup rate is inherited, accumulators always start out empty
+impl<T> GeneEncoder for Accumulator<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    fn encode_payload(&self) -> Result<Vec<AminoAcid>, String> {
+        Ok(rate_payload(self.buildup_rate))
+    }
+
+    // Written as "rate <number>"
+    fn payload_from_text(text: &str) -> Result<Vec<AminoAcid>, String> {
+        match text.split_whitespace().collect::<Vec<_>>()[..] {
+            ["rate", rate] => rate
+                .parse::<u32>()
+                .map(rate_payload)
+                .map_err(|_| format!("{:?} is not a rate", rate)),
+            _ => Err("expected 'rate <number>'".to_string()),
+        }
+    }
+
+    fn payload_to_text(payload: &[AminoAcid]) -> String {
+        format!("rate {}", rate_from_payload(payload))
+    }
+}
 
 #[cfg(test)]
 mod test {
     use gene_traits::amino_acid::AminoAcid;
 
-    use crate::components::Dopamine;
+    use gene_traits::mutation::seeded_rng;
+    use rand::Rng;
 
-    use super::accumulator_sequence_parser;
+    use crate::component_register::{GeneEncoder, encode_gene};
+    use crate::components::{Dopamine, Serotonin};
+
+    use super::{Accumulator, accumulator_sequence_parser};
 
     #[test]
     pub fn parse_accumulator_gene() {
@@ -147,41 +128,39 @@ mod test {
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
+            AminoAcid::STOP,
         ];
 
         let (accumulator, consumed) = accumulator_sequence_parser::<Dopamine>(&sequence);
 
         assert_eq!(accumulator.buildup_rate, expected);
-        assert_eq!(consumed, 8);
+        assert_eq!(consumed, 5);
     }
 
     #[test]
-    pub fn parse_accumulator_gene_no_tata() {
+    pub fn parse_accumulator_gene_no_stop() {
         let expected = 4;
 
         let sequence = [AminoAcid::R, AminoAcid::R, AminoAcid::R, AminoAcid::R];
 
-        let (accumulator, _) = accumulator_sequence_parser::<Dopamine>(&sequence);
+        let (accumulator, consumed) = accumulator_sequence_parser::<Dopamine>(&sequence);
 
         assert_eq!(accumulator.buildup_rate, expected);
+        assert_eq!(consumed, 4);
     }
 
     #[test]
-    pub fn parse_accumulator_gene_partial_tata() {
+    pub fn parse_accumulator_gene_skips_valueless_codons() {
         let expected = 4;
 
         let sequence = [
             AminoAcid::R,
             AminoAcid::R,
+            AminoAcid::START,
+            AminoAcid::UNKNOWN,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
+            AminoAcid::STOP,
         ];
 
         let (accumulator, consumed) = accumulator_sequence_parser::<Dopamine>(&sequence);
@@ -191,25 +170,23 @@ mod test {
     }
 
     #[test]
-    pub fn parse_accumulator_gene_invalid_tata() {
-        let expected = 6;
+    pub fn parse_accumulator_gene_alanine_is_zero() {
+        let expected = 4;
 
         let sequence = [
+            AminoAcid::A,
+            AminoAcid::A,
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::R,
-            AminoAcid::R,
+            AminoAcid::STOP,
         ];
 
         let (accumulator, consumed) = accumulator_sequence_parser::<Dopamine>(&sequence);
 
         assert_eq!(accumulator.buildup_rate, expected);
-        assert_eq!(consumed, 9);
+        assert_eq!(consumed, 7);
     }
 
     #[test]
@@ -221,10 +198,7 @@ mod test {
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
+            AminoAcid::STOP,
             AminoAcid::R,
             AminoAcid::R,
         ];
@@ -232,6 +206,28 @@ mod test {
         let (accumulator, consumed) = accumulator_sequence_parser::<Dopamine>(&sequence);
 
         assert_eq!(accumulator.buildup_rate, expected);
-        assert_eq!(consumed, 8);
+        assert_eq!(consumed, 5);
+    }
+
+    #[test]
+    pub fn encoded_rates_parse_back() {
+        let mut rng = seeded_rng(16);
+        for rate in (0..60).chain((0..100).map(|_| rng.random_range(0..5000))) {
+            let accumulator = Accumulator::<Dopamine>::new(7, rate);
+            let mut payload = accumulator.encode_payload().unwrap();
+            payload.push(AminoAcid::STOP);
+
+            let (parsed, consumed) = accumulator_sequence_parser::<Dopamine>(&payload);
+
+            assert_eq!(parsed.buildup_rate, rate);
+            assert_eq!(parsed.level, 0);
+            assert_eq!(consumed, payload.len());
+        }
+
+        // Each neurotransmitter gets the header of its own gene
+        assert_ne!(
+            encode_gene(&Accumulator::<Dopamine>::new(0, 4)).unwrap(),
+            encode_gene(&Accumulator::<Serotonin>::new(0, 4)).unwrap()
+        );
     }
 }
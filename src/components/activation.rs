@@ -1,84 +1,102 @@
-use crate::components::expr_gene::{expr_from_amino_acids, last_idx_before_promoter};
-use crate::config::PROMOTER_SIZE;
-use crate::ComponentRegister;
+use crate::component_register::{GeneEncoder, GeneParseError};
+use crate::components::compiled_expr::CompiledExpr;
+use crate::components::expr_gene::{
+    amino_acids_from_expr, amino_acids_from_node, consumed_through_stop, expr_from_amino_acids,
+    last_idx_before_stop, ExprKind,
+};
+use crate::components::expr_variables::ExprVariables;
 use bevy::ecs::{
     component::Component,
     system::EntityCommands,
 };
 use evalexpr::*;
 use gene_traits::amino_acid::AminoAcid;
-use gene_traits::dna::get_header;
-use gene_traits::mul;
-use gene_traits::register_gene;
-use hashed_type_def::HashedTypeDef;
-
-use super::Neuron;
-
-// Okay... this is kind of annoying, but it turns out not all types can just derive HashedTypeDef,
-// and it is possible to create a 'tag' type that can act as a 'marker' for the type in the registration macro.
-#[derive(HashedTypeDef)]
-pub struct ActivationTag {}
+use gene_traits::header::Gene;
 
-#[derive(Component)]
+#[derive(Component, Gene)]
+// Version 2 genes are typed prefix expressions
+#[gene(id = "activation", version = 2, salt = 0)]
 pub struct Activation {
     // Amazingly, there's an expression evaluator already.  Just need to parse the genes to strings...
-    pub activation: Node<DefaultNumericTypes>,
+    activation: Node<DefaultNumericTypes>,
+    // Evaluated every tick, the tree is kept for writing the gene back out
+    compiled: CompiledExpr,
 }
 
 impl Activation {
-    pub fn get_activation(&self, neuron: &Neuron) -> bool {
-        let context = context_map! {
-            "dopamine" => int neuron.dopamine,
-            "serotonin" => int neuron.serotonin,
-            "norepinephrine" => int neuron.norepinephrine
-        }
-            .unwrap();
-        self.activation
-            //.as_ref()
-            //.expect("There must be an activation function")
-            .eval_boolean_with_context(&context)
-            .expect("Function must return a valid boolean")
+    // Activation genes spell a condition
+    pub const EXPR_KIND: ExprKind = ExprKind::Bool;
+
+    pub fn new(activation: Node<DefaultNumericTypes>) -> Result<Self, String> {
+        let compiled = CompiledExpr::compile(&activation)?;
+        Ok(Self {
+            activation,
+            compiled,
+        })
     }
 
-    pub fn sequence_parser(sequence: &[AminoAcid], mut commands: EntityCommands) -> usize {
-        let last_idx = last_idx_before_promoter(sequence, PROMOTER_SIZE);
+    pub fn expr(&self) -> &Node<DefaultNumericTypes> {
+        &self.activation
+    }
 
-        let formula = expr_from_amino_acids(&sequence[0..last_idx]);
+    pub fn get_activation(&self, variables: &ExprVariables) -> bool {
+        // Genes always spell a condition, but it can still fail to evaluate, e.g. on a division by
+        // zero.  A neuron whose condition fails doesn't fire.
+        self.compiled.eval_bool(variables).unwrap_or(false)
+    }
 
-        let precompiled = build_operator_tree::<DefaultNumericTypes>(&formula)
-            .expect("Failed to precompile activation function");
+    pub fn sequence_parser(
+        sequence: &[AminoAcid],
+        mut commands: EntityCommands,
+    ) -> Result<usize, GeneParseError> {
+        let last_idx = last_idx_before_stop(sequence);
+        let consumed = consumed_through_stop(sequence, last_idx);
 
-        let activation = Activation {
-            activation: precompiled,
-        };
+        let formula = expr_from_amino_acids(&sequence[0..last_idx], Self::EXPR_KIND);
+
+        let activation = build_operator_tree::<DefaultNumericTypes>(&formula)
+            .map_err(|e| e.to_string())
+            .and_then(Activation::new)
+            .map_err(|e| GeneParseError {
+                consumed,
+                message: format!("activation {:?} doesn't compile: {}", formula, e),
+            })?;
 
         commands.insert(activation);
 
-        last_idx
+        Ok(consumed)
     }
 }
 
-pub fn activation_parser(sequence: &[AminoAcid], commands: EntityCommands) -> usize {
-    Activation::sequence_parser(sequence, commands)
-}
+impl GeneEncoder for Activation {
+    fn encode_payload(&self) -> Result<Vec<AminoAcid>, String> {
+        amino_acids_from_node(&self.activation, Self::EXPR_KIND)
+    }
+
+    fn payload_from_text(text: &str) -> Result<Vec<AminoAcid>, String> {
+        amino_acids_from_expr(text, Self::EXPR_KIND)
+    }
 
-register_gene!(
-    Activation,
-    { ActivationTag::TYPE_HASH_NATIVE },
-    activation_parser,
-    { PROMOTER_SIZE }
-);
+    fn payload_to_text(payload: &[AminoAcid]) -> String {
+        expr_from_amino_acids(payload, Self::EXPR_KIND)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use evalexpr::{build_operator_tree, DefaultNumericTypes};
+    use gene_traits::mutation::seeded_rng;
 
+    use crate::component_register::encode_gene;
+    use crate::components::expr_gene::{amino_acids_from_node, random_expr};
+    use crate::components::amino_acid_reader::{AminoAcidChain, AminoAcidReader};
     use crate::components::Activation;
+    use crate::systems::ribosome::parse_amino_acid_strand;
 
     use bevy::app::{App, Update};
     use bevy::ecs::world::World;
 
-    use crate::components::{Neuron, Synapse};
+    use crate::components::{Dopamine, Neuron, Synapse};
     use crate::systems::neuron_updates::update_synapse;
 
     #[test]
@@ -87,31 +105,25 @@ mod tests {
         app.add_systems(Update, update_synapse);
 
         // Activation formula: dopamine > 0
-        let activation_true = Activation {
-            activation: build_operator_tree::<DefaultNumericTypes>("dopamine>0").unwrap(),
-        };
+        let activation_true =
+            Activation::new(build_operator_tree::<DefaultNumericTypes>("dopamine>0").unwrap())
+                .unwrap();
 
         // Entity where activation should be true
         app.world_mut().spawn((
-            Neuron {
-                dopamine: 5,
-                ..Default::default()
-            },
+            Neuron::default().with_level::<Dopamine>(5),
             Synapse::default(),
             activation_true,
         ));
 
         // Activation formula: dopamine > 0 (same), but dopamine is 0, so false
-        let activation_false = Activation {
-            activation: build_operator_tree::<DefaultNumericTypes>("dopamine>0").unwrap(),
-        };
+        let activation_false =
+            Activation::new(build_operator_tree::<DefaultNumericTypes>("dopamine>0").unwrap())
+                .unwrap();
 
         // Entity where activation should be false
         app.world_mut().spawn((
-            Neuron {
-                dopamine: 0,
-                ..Default::default()
-            },
+            Neuron::default(),
             Synapse::default(),
             activation_false,
         ));
@@ -129,13 +141,13 @@ mod tests {
             if synapse.active {
                 saw_true = true;
                 assert_eq!(
-                    neuron.dopamine, 0,
+                    neuron.level::<Dopamine>(), 0,
                     "Active synapse should reset dopamine to 0"
                 );
             } else {
                 saw_false = true;
                 assert_eq!(
-                    neuron.dopamine, 0,
+                    neuron.level::<Dopamine>(), 0,
                     "Inactive synapse should leave dopamine unchanged (expected 0)"
                 );
             }
@@ -144,4 +156,40 @@ mod tests {
         assert!(saw_true, "No entity had an active synapse");
         assert!(saw_false, "No entity had an inactive synapse");
     }
+
+    #[test]
+    fn encoded_activations_parse_back() {
+        let mut rng = seeded_rng(16);
+        let mut app = App::new();
+        app.add_systems(Update, parse_amino_acid_strand);
+
+        let mut encoded = Vec::new();
+        while encoded.len() < 50 {
+            let expr = random_expr(&mut rng, 3, Activation::EXPR_KIND);
+            let Ok(activation) = build_operator_tree::<DefaultNumericTypes>(&expr) else {
+                continue;
+            };
+            let chain = encode_gene(&Activation::new(activation).unwrap()).unwrap();
+            let entity = app
+                .world_mut()
+                .spawn((AminoAcidReader, AminoAcidChain(chain.clone())))
+                .id();
+            encoded.push((entity, chain));
+        }
+
+        app.update();
+
+        // Parsed activations encode to the same chain, the property test in expr_gene checks they
+        // evaluate the same as well
+        let mut query = app.world_mut().query::<&Activation>();
+        let parsed: Vec<_> = query
+            .iter(app.world())
+            .map(|activation| amino_acids_from_node(&activation.activation, Activation::EXPR_KIND).unwrap())
+            .collect();
+        assert_eq!(parsed.len(), encoded.len());
+        for (_, chain) in &encoded {
+            let payload = &chain[crate::config::PROMOTER_SIZE..chain.len() - 1];
+            assert!(parsed.iter().any(|p| p == payload));
+        }
+    }
 }
@@ -1,8 +1,39 @@
+use std::ops::Range;
+
 use bevy::{ecs::component::Component, prelude::Deref};
-use gene_traits::amino_acid::AminoAcid;
+use gene_traits::{amino_acid::AminoAcid, header::GeneId};
 
 #[derive(Component)]
 pub struct AminoAcidReader;
 
 #[derive(Component, Deref)]
 pub struct AminoAcidChain(pub Vec<AminoAcid>);
+
+// What became of one gene of a chain.  Ranges are indices into the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneOutcome {
+    pub id: GeneId,
+    pub type_str: &'static str,
+    // Levenshtein distance between the registered header and the one in the chain
+    pub distance: usize,
+    pub header: Range<usize>,
+    // What the parser consumed after the header, the stop codon included
+    pub consumed: Range<usize>,
+    // Why the gene didn't build a component
+    pub error: Option<String>,
+}
+
+/**
+ * Left on a chain once the ribosome has read it, with every gene it found in chain order.  Broken
+ * genes are recorded here and skipped, the rest of the chain is still read.
+ */
+#[derive(Component, Debug, Default, Clone)]
+pub struct ParseReport {
+    pub genes: Vec<GeneOutcome>,
+}
+
+impl ParseReport {
+    pub fn failures(&self) -> impl Iterator<Item = &GeneOutcome> {
+        self.genes.iter().filter(|gene| gene.error.is_some())
+    }
+}
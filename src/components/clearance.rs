@@ -0,0 +1,323 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::Component;
+use gene_traits::amino_acid::AminoAcid;
+use std::fmt::{self, Debug, Display};
+use std::marker::PhantomData;
+
+use crate::component_register::{GeneEncoder, GeneParseError};
+use crate::components::expr_gene::{consumed_through_stop, last_idx_before_stop};
+
+// The largest value a clearance gene spells for a parameter, two amino acids in base 20
+pub const PARAM_MAX: u32 = 399;
+
+/**
+ * How a neurotransmitter is cleared out of a neuron every tick.  Only the part of the level above
+ * the resting level is cleared, by three mechanisms working side by side:
+ *  - decay, losing a fixed fraction of it, which makes the level fall exponentially
+ *  - reuptake, which takes up to reuptake_max but saturates, running at half speed when the
+ *    excess is reuptake_half
+ *  - enzymatic degradation, breaking down a fixed amount
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClearanceParams {
+    pub resting_level: u32,
+    // Per mille of the excess lost every tick
+    pub decay: u32,
+    pub reuptake_max: u32,
+    pub reuptake_half: u32,
+    pub degradation: u32,
+}
+
+impl Default for ClearanceParams {
+    // Roughly halves an excess of 100 in five ticks and clears small ones within a few more
+    fn default() -> Self {
+        Self {
+            resting_level: 0,
+            decay: 50,
+            reuptake_max: 20,
+            reuptake_half: 100,
+            degradation: 1,
+        }
+    }
+}
+
+impl ClearanceParams {
+    // The names the parameters are written with, in the order genes spell them
+    pub const NAMES: [&'static str; 5] = [
+        "resting",
+        "decay",
+        "reuptake_max",
+        "reuptake_half",
+        "degradation",
+    ];
+
+    fn values(&self) -> [u32; 5] {
+        [
+            self.resting_level,
+            self.decay,
+            self.reuptake_max,
+            self.reuptake_half,
+            self.degradation,
+        ]
+    }
+
+    fn from_values(values: [u32; 5]) -> Self {
+        let [
+            resting_level,
+            decay,
+            reuptake_max,
+            reuptake_half,
+            degradation,
+        ] = values;
+        Self {
+            resting_level,
+            decay,
+            reuptake_max,
+            reuptake_half,
+            degradation,
+        }
+    }
+
+    // How much of the level is cleared in one tick, never more than the excess over resting
+    pub fn cleared(&self, level: u32) -> u32 {
+        let excess = level.saturating_sub(self.resting_level);
+        if excess == 0 {
+            return 0;
+        }
+        let x = f64::from(excess);
+        let decay = x * f64::from(self.decay) / 1000.0;
+        let reuptake = f64::from(self.reuptake_max) * x / (f64::from(self.reuptake_half) + x);
+        let cleared = (decay + reuptake + f64::from(self.degradation)).round();
+        // Saturating cast, anything past the excess is capped anyway
+        (cleared as u32).min(excess)
+    }
+
+    /**
+     * Reads the parameters the way Display writes them, e.g. "resting 0 decay 50 reuptake_max 20
+     * reuptake_half 100 degradation 1".  Parameters that are left out are 0.
+     */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut values = [0; 5];
+        let words: Vec<&str> = text.split_whitespace().collect();
+        for pair in words.chunks(2) {
+            let [name, value] = pair else {
+                return Err(format!("{} has no value", pair[0]));
+            };
+            let index = Self::NAMES
+                .iter()
+                .position(|known| known == name)
+                .ok_or_else(|| format!("{:?} is not a clearance parameter", name))?;
+            values[index] = value
+                .parse()
+                .map_err(|_| format!("{:?} is not a number", value))?;
+        }
+        Ok(Self::from_values(values))
+    }
+}
+
+impl Display for ClearanceParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let written: Vec<String> = Self::NAMES
+            .iter()
+            .zip(self.values())
+            .map(|(name, value)| format!("{} {}", name, value))
+            .collect();
+        write!(f, "{}", written.join(" "))
+    }
+}
+
+// Each parameter is spelled by a pair of amino acids, acids without a value count as 0 and so do
+// parameters cut off by the end of the gene
+pub fn params_from_amino_acids(payload: &[AminoAcid]) -> ClearanceParams {
+    let value = |acid: AminoAcid| u8::try_from(acid).map_or(0, u32::from);
+    let mut values = [0; 5];
+    for (slot, pair) in values.iter_mut().zip(payload.chunks_exact(2)) {
+        *slot = value(pair[0]) * 20 + value(pair[1]);
+    }
+    ClearanceParams::from_values(values)
+}
+
+pub fn amino_acids_from_params(params: &ClearanceParams) -> Result<Vec<AminoAcid>, String> {
+    let acid = |value: u32| AminoAcid::try_from(value as u8).expect("Below 20");
+    let mut acids = Vec::new();
+    for (name, value) in ClearanceParams::NAMES.iter().zip(params.values()) {
+        if value > PARAM_MAX {
+            return Err(format!("{} {} is above {}", name, value, PARAM_MAX));
+        }
+        acids.extend_from_slice(&[acid(value / 20), acid(value % 20)]);
+    }
+    Ok(acids)
+}
+
+/**
+ * Overrides the global clearance of a neurotransmitter for the neuron it sits on.  Neurons without
+ * one are cleared with the DefaultClearance resource.  Registered as a gene once per
+ * neurotransmitter by neurotransmitters!
+ */
+#[derive(Component, Default, Debug)]
+pub struct Clearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    pub params: ClearanceParams,
+    pub _phantom: PhantomData<T>,
+}
+
+impl<T> Clearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    pub fn new(params: ClearanceParams) -> Self {
+        Self {
+            params,
+            _phantom: PhantomData,
+        }
+    }
+
+    // Every payload spells some parameters, so clearance genes never fail
+    pub fn sequence_parser(
+        gene: &[AminoAcid],
+        mut commands: EntityCommands,
+    ) -> Result<usize, GeneParseError>
+    where
+        T: 'static,
+    {
+        let last_index = last_idx_before_stop(gene);
+        let consumed = consumed_through_stop(gene, last_index);
+
+        commands.insert(Self::new(params_from_amino_acids(&gene[..last_index])));
+        Ok(consumed)
+    }
+}
+
+impl<T> GeneEncoder for Clearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    fn encode_payload(&self) -> Result<Vec<AminoAcid>, String> {
+        amino_acids_from_params(&self.params)
+    }
+
+    fn payload_from_text(text: &str) -> Result<Vec<AminoAcid>, String> {
+        ClearanceParams::parse(text).and_then(|params| amino_acids_from_params(&params))
+    }
+
+    fn payload_to_text(payload: &[AminoAcid]) -> String {
+        params_from_amino_acids(payload).to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gene_traits::amino_acid::AminoAcid;
+    use gene_traits::mutation::seeded_rng;
+    use rand::Rng;
+
+    use super::{ClearanceParams, PARAM_MAX, amino_acids_from_params, params_from_amino_acids};
+
+    #[test]
+    fn clearance_never_goes_below_resting() {
+        let params = ClearanceParams {
+            resting_level: 10,
+            ..Default::default()
+        };
+        assert_eq!(params.cleared(0), 0);
+        assert_eq!(params.cleared(10), 0);
+        assert_eq!(params.cleared(11), 1);
+
+        let mut level = 1000;
+        for _ in 0..200 {
+            level -= params.cleared(level);
+        }
+        assert_eq!(level, 10);
+    }
+
+    #[test]
+    fn mechanisms_add_up() {
+        let only = |params: ClearanceParams| params.cleared(1100);
+        let none = ClearanceParams {
+            resting_level: 100,
+            decay: 0,
+            reuptake_max: 0,
+            reuptake_half: 0,
+            degradation: 0,
+        };
+        assert_eq!(only(none), 0);
+        // Exponential decay takes a fraction of the excess
+        assert_eq!(only(ClearanceParams { decay: 100, ..none }), 100);
+        // Reuptake runs at half speed when the excess is reuptake_half, and saturates
+        assert_eq!(
+            only(ClearanceParams {
+                reuptake_max: 40,
+                reuptake_half: 1000,
+                ..none
+            }),
+            20
+        );
+        assert_eq!(
+            ClearanceParams {
+                reuptake_max: 40,
+                reuptake_half: 10,
+                ..none
+            }
+            .cleared(1_000_000),
+            40
+        );
+        assert_eq!(
+            only(ClearanceParams {
+                degradation: 7,
+                ..none
+            }),
+            7
+        );
+        assert_eq!(
+            only(ClearanceParams {
+                decay: 100,
+                reuptake_max: 40,
+                reuptake_half: 1000,
+                degradation: 7,
+                ..none
+            }),
+            127
+        );
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let mut rng = seeded_rng(23);
+        for _ in 0..100 {
+            let params = ClearanceParams {
+                resting_level: rng.random_range(0..=PARAM_MAX),
+                decay: rng.random_range(0..=PARAM_MAX),
+                reuptake_max: rng.random_range(0..=PARAM_MAX),
+                reuptake_half: rng.random_range(0..=PARAM_MAX),
+                degradation: rng.random_range(0..=PARAM_MAX),
+            };
+            let acids = amino_acids_from_params(&params).unwrap();
+            assert_eq!(params_from_amino_acids(&acids), params);
+            assert_eq!(ClearanceParams::parse(&params.to_string()), Ok(params));
+        }
+
+        // Missing parameters are 0
+        assert_eq!(
+            params_from_amino_acids(&[AminoAcid::R, AminoAcid::R, AminoAcid::R]),
+            ClearanceParams::parse("resting 21").unwrap()
+        );
+        assert!(
+            amino_acids_from_params(&ClearanceParams {
+                decay: 400,
+                ..Default::default()
+            })
+            .is_err()
+        );
+        assert!(ClearanceParams::parse("decay").is_err());
+        assert!(ClearanceParams::parse("decay fast").is_err());
+        assert!(ClearanceParams::parse("half_life 3").is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use evalexpr::{DefaultNumericTypes, Node, Operator, Value};
+
+use crate::components::expr_variables::ExprVariables;
+
+// What a compiled expression evaluates to, the values evalexpr can produce from a gene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExprValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+// Evaluation errors are where evalexpr would have failed, only the cause is kept so that failing
+// doesn't allocate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    // Integer arithmetic that overflows or divides by zero
+    Arithmetic,
+    // An operand of the wrong type, like a number where a condition was expected
+    WrongType,
+}
+
+impl ExprValue {
+    fn as_number(self) -> Result<f64, EvalError> {
+        match self {
+            ExprValue::Int(int) => Ok(int as f64),
+            ExprValue::Float(float) => Ok(float),
+            ExprValue::Bool(_) => Err(EvalError::WrongType),
+        }
+    }
+
+    pub fn as_bool(self) -> Result<bool, EvalError> {
+        match self {
+            ExprValue::Bool(boolean) => Ok(boolean),
+            _ => Err(EvalError::WrongType),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binary {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Exp,
+    Eq,
+    Neq,
+    Gt,
+    Lt,
+    Geq,
+    Leq,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Sin,
+    Cos,
+    Ln,
+    Log,
+    Log2,
+    Min,
+    Max,
+    Clamp,
+    If,
+    Sigmoid,
+    Tanh,
+    Abs,
+}
+
+// The functions that can be compiled, with the number of arguments they are called with
+const FUNCTIONS: [(&str, Function, usize); 12] = [
+    ("math::sin", Function::Sin, 1),
+    ("math::cos", Function::Cos, 1),
+    ("math::ln", Function::Ln, 1),
+    ("math::log", Function::Log, 2),
+    ("math::log2", Function::Log2, 1),
+    ("min", Function::Min, 2),
+    ("max", Function::Max, 2),
+    ("clamp", Function::Clamp, 3),
+    ("if", Function::If, 3),
+    ("sigmoid", Function::Sigmoid, 1),
+    ("math::tanh", Function::Tanh, 1),
+    ("math::abs", Function::Abs, 1),
+];
+
+// Operands refer to earlier ops by their index
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Const(ExprValue),
+    // A slot in ExprVariables::values
+    Variable(usize),
+    Neg(usize),
+    Not(usize),
+    Binary(Binary, usize, usize),
+    // Takes the first arity operands, the rest are left at 0
+    Call(Function, usize, [usize; 3]),
+}
+
+/**
+ * An expression compiled for evaluation.  Variables are resolved to slots and functions to ops
+ * when compiling, so evaluating neither looks anything up by name nor allocates.  Results match
+ * evaluating the tree with evalexpr under ExprVariables::context, with the exception that
+ * evalexpr's errors carry more detail.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledExpr {
+    // Operands come before the ops using them, the last op is the root
+    ops: Vec<Op>,
+}
+
+// Root nodes only group their child
+fn unwrap_root(node: &Node<DefaultNumericTypes>) -> &Node<DefaultNumericTypes> {
+    match (node.operator(), node.children()) {
+        (Operator::RootNode, [child]) => unwrap_root(child),
+        _ => node,
+    }
+}
+
+fn binary(operator: &Operator<DefaultNumericTypes>) -> Option<Binary> {
+    Some(match operator {
+        Operator::Add => Binary::Add,
+        Operator::Sub => Binary::Sub,
+        Operator::Mul => Binary::Mul,
+        Operator::Div => Binary::Div,
+        Operator::Mod => Binary::Mod,
+        Operator::Exp => Binary::Exp,
+        Operator::Eq => Binary::Eq,
+        Operator::Neq => Binary::Neq,
+        Operator::Gt => Binary::Gt,
+        Operator::Lt => Binary::Lt,
+        Operator::Geq => Binary::Geq,
+        Operator::Leq => Binary::Leq,
+        Operator::And => Binary::And,
+        Operator::Or => Binary::Or,
+        _ => return None,
+    })
+}
+
+impl CompiledExpr {
+    /**
+     * Compiles everything an expression gene can spell.  Trees using anything else, like
+     * assignments, strings or variables no gene reads, are refused.
+     */
+    pub fn compile(node: &Node<DefaultNumericTypes>) -> Result<Self, String> {
+        let mut compiled = CompiledExpr { ops: Vec::new() };
+        compiled.push_node(node)?;
+        Ok(compiled)
+    }
+
+    fn push(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    fn push_node(&mut self, node: &Node<DefaultNumericTypes>) -> Result<usize, String> {
+        let node = unwrap_root(node);
+        let op = match (node.operator(), node.children()) {
+            (Operator::Const { value }, []) => Op::Const(match value {
+                Value::Int(int) => ExprValue::Int(*int),
+                Value::Float(float) => ExprValue::Float(*float),
+                Value::Boolean(boolean) => ExprValue::Bool(*boolean),
+                _ => return Err(format!("{} isn't a number or a condition", value)),
+            }),
+            (Operator::VariableIdentifierRead { identifier }, []) => Op::Variable(
+                ExprVariables::NAMES
+                    .iter()
+                    .position(|name| name == identifier)
+                    .ok_or_else(|| format!("there is no variable {}", identifier))?,
+            ),
+            (Operator::Neg, [operand]) => Op::Neg(self.push_node(operand)?),
+            (Operator::Not, [operand]) => Op::Not(self.push_node(operand)?),
+            (Operator::FunctionIdentifier { identifier }, [argument]) => {
+                let argument = unwrap_root(argument);
+                let arguments = match argument.operator() {
+                    Operator::Tuple => argument.children(),
+                    _ => std::slice::from_ref(argument),
+                };
+                let (_, function, arity) = FUNCTIONS
+                    .iter()
+                    .find(|(name, _, arity)| name == identifier && *arity == arguments.len())
+                    .ok_or_else(|| {
+                        format!(
+                            "{} can't be called with {} arguments",
+                            identifier,
+                            arguments.len()
+                        )
+                    })?;
+                let mut operands = [0; 3];
+                for (operand, argument) in operands.iter_mut().zip(arguments) {
+                    *operand = self.push_node(argument)?;
+                }
+                Op::Call(*function, *arity, operands)
+            }
+            (operator, [left, right]) if binary(operator).is_some() => {
+                let left = self.push_node(left)?;
+                let right = self.push_node(right)?;
+                Op::Binary(binary(operator).expect("Checked above"), left, right)
+            }
+            _ => return Err(format!("{} can't be compiled", node)),
+        };
+        Ok(self.push(op))
+    }
+
+    pub fn eval(&self, variables: &ExprVariables) -> Result<ExprValue, EvalError> {
+        self.eval_op(self.ops.len() - 1, &variables.values())
+    }
+
+    // Conditions that evaluate to anything but a boolean are errors, as with evalexpr
+    pub fn eval_bool(&self, variables: &ExprVariables) -> Result<bool, EvalError> {
+        self.eval(variables)?.as_bool()
+    }
+
+    fn eval_op(&self, index: usize, slots: &[i64]) -> Result<ExprValue, EvalError> {
+        match self.ops[index] {
+            Op::Const(value) => Ok(value),
+            Op::Variable(slot) => Ok(ExprValue::Int(slots[slot])),
+            Op::Neg(operand) => match self.eval_op(operand, slots)? {
+                ExprValue::Int(int) => int
+                    .checked_neg()
+                    .map(ExprValue::Int)
+                    .ok_or(EvalError::Arithmetic),
+                ExprValue::Float(float) => Ok(ExprValue::Float(-float)),
+                ExprValue::Bool(_) => Err(EvalError::WrongType),
+            },
+            Op::Not(operand) => Ok(ExprValue::Bool(!self.eval_op(operand, slots)?.as_bool()?)),
+            Op::Binary(operator, left, right) => binary_op(
+                operator,
+                self.eval_op(left, slots)?,
+                self.eval_op(right, slots)?,
+            ),
+            Op::Call(function, arity, operands) => {
+                // Every argument is evaluated, even the branch if doesn't take
+                let mut arguments = [ExprValue::Int(0); 3];
+                for (argument, &operand) in arguments.iter_mut().zip(&operands[..arity]) {
+                    *argument = self.eval_op(operand, slots)?;
+                }
+                call(function, &arguments[..arity])
+            }
+        }
+    }
+}
+
+// Integer arithmetic is checked, anything with a float in it is done in floats
+fn arithmetic(
+    left: ExprValue,
+    right: ExprValue,
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+) -> Result<ExprValue, EvalError> {
+    match (left, right) {
+        (ExprValue::Int(a), ExprValue::Int(b)) => {
+            int(a, b).map(ExprValue::Int).ok_or(EvalError::Arithmetic)
+        }
+        _ => Ok(ExprValue::Float(float(
+            left.as_number()?,
+            right.as_number()?,
+        ))),
+    }
+}
+
+fn compare(
+    left: ExprValue,
+    right: ExprValue,
+    int: fn(&i64, &i64) -> bool,
+    float: fn(&f64, &f64) -> bool,
+) -> Result<ExprValue, EvalError> {
+    Ok(ExprValue::Bool(match (left, right) {
+        (ExprValue::Int(a), ExprValue::Int(b)) => int(&a, &b),
+        _ => float(&left.as_number()?, &right.as_number()?),
+    }))
+}
+
+fn binary_op(operator: Binary, left: ExprValue, right: ExprValue) -> Result<ExprValue, EvalError> {
+    match operator {
+        Binary::Add => arithmetic(left, right, i64::checked_add, |a, b| a + b),
+        Binary::Sub => arithmetic(left, right, i64::checked_sub, |a, b| a - b),
+        Binary::Mul => arithmetic(left, right, i64::checked_mul, |a, b| a * b),
+        Binary::Div => arithmetic(left, right, i64::checked_div, |a, b| a / b),
+        Binary::Mod => arithmetic(left, right, i64::checked_rem, |a, b| a % b),
+        // Powers are always taken in floats
+        Binary::Exp => Ok(ExprValue::Float(left.as_number()?.powf(right.as_number()?))),
+        // Values of different types are never equal, not even 1 and 1.0
+        Binary::Eq => Ok(ExprValue::Bool(left == right)),
+        Binary::Neq => Ok(ExprValue::Bool(left != right)),
+        Binary::Gt => compare(left, right, i64::gt, f64::gt),
+        Binary::Lt => compare(left, right, i64::lt, f64::lt),
+        Binary::Geq => compare(left, right, i64::ge, f64::ge),
+        Binary::Leq => compare(left, right, i64::le, f64::le),
+        Binary::And => Ok(ExprValue::Bool(left.as_bool()? && right.as_bool()?)),
+        Binary::Or => Ok(ExprValue::Bool(left.as_bool()? || right.as_bool()?)),
+    }
+}
+
+fn call(function: Function, arguments: &[ExprValue]) -> Result<ExprValue, EvalError> {
+    let float = |f: fn(f64) -> f64| Ok(ExprValue::Float(f(arguments[0].as_number()?)));
+    match function {
+        Function::Sin => float(f64::sin),
+        Function::Cos => float(f64::cos),
+        Function::Ln => float(f64::ln),
+        Function::Log2 => float(f64::log2),
+        Function::Tanh => float(f64::tanh),
+        Function::Sigmoid => float(|x| 1.0 / (1.0 + (-x).exp())),
+        Function::Log => Ok(ExprValue::Float(
+            arguments[0].as_number()?.log(arguments[1].as_number()?),
+        )),
+        Function::Abs => match arguments[0] {
+            // evalexpr leaves the overflow of the smallest integer to i64::abs
+            ExprValue::Int(int) => Ok(ExprValue::Int(int.wrapping_abs())),
+            ExprValue::Float(float) => Ok(ExprValue::Float(float.abs())),
+            ExprValue::Bool(_) => Err(EvalError::WrongType),
+        },
+        Function::Min | Function::Max => extremum(function == Function::Min, arguments),
+        Function::Clamp => match *arguments {
+            [ExprValue::Int(x), ExprValue::Int(low), ExprValue::Int(high)] => {
+                Ok(ExprValue::Int(x.min(high).max(low)))
+            }
+            _ => {
+                let (x, low, high) = (
+                    arguments[0].as_number()?,
+                    arguments[1].as_number()?,
+                    arguments[2].as_number()?,
+                );
+                Ok(ExprValue::Float(x.min(high).max(low)))
+            }
+        },
+        Function::If => Ok(if arguments[0].as_bool()? {
+            arguments[1]
+        } else {
+            arguments[2]
+        }),
+    }
+}
+
+// evalexpr keeps the integer and the float extremes apart, and only answers with the integer one
+// when it is strictly beyond the float one
+fn extremum(min: bool, arguments: &[ExprValue]) -> Result<ExprValue, EvalError> {
+    let (mut int, mut float) = if min {
+        (i64::MAX, f64::INFINITY)
+    } else {
+        (i64::MIN, f64::NEG_INFINITY)
+    };
+    for argument in arguments {
+        match (*argument, min) {
+            (ExprValue::Int(value), true) => int = int.min(value),
+            (ExprValue::Int(value), false) => int = int.max(value),
+            (ExprValue::Float(value), true) => float = float.min(value),
+            (ExprValue::Float(value), false) => float = float.max(value),
+            (ExprValue::Bool(_), _) => return Err(EvalError::WrongType),
+        }
+    }
+    let int_wins = if min {
+        (int as f64) < float
+    } else {
+        (int as f64) > float
+    };
+    Ok(if int_wins {
+        ExprValue::Int(int)
+    } else {
+        ExprValue::Float(float)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use evalexpr::{DefaultNumericTypes, Value, build_operator_tree};
+    use gene_traits::mutation::seeded_rng;
+    use rand::Rng;
+
+    use std::array::from_fn;
+
+    use super::{CompiledExpr, ExprValue};
+    use crate::components::{
+        Dopamine,
+        expr_gene::{ExprKind, random_expr},
+        expr_variables::{ExprVariables, RANDOM_RANGE},
+    };
+
+    // What evalexpr makes of the expression, in the same terms
+    fn reference(expr: &str, variables: &ExprVariables) -> Option<ExprValue> {
+        let tree = build_operator_tree::<DefaultNumericTypes>(expr).unwrap();
+        match tree.eval_with_context(&variables.context()).ok()? {
+            Value::Int(int) => Some(ExprValue::Int(int)),
+            Value::Float(float) => Some(ExprValue::Float(float)),
+            Value::Boolean(boolean) => Some(ExprValue::Bool(boolean)),
+            value => panic!("{} evaluated to {}", expr, value),
+        }
+    }
+
+    fn same(a: Option<ExprValue>, b: Option<ExprValue>) -> bool {
+        match (a, b) {
+            (Some(ExprValue::Float(a)), Some(ExprValue::Float(b))) => {
+                a == b || (a.is_nan() && b.is_nan())
+            }
+            _ => a == b,
+        }
+    }
+
+    // Small values hit the edges, like dividing by zero, more often
+    fn small(rng: &mut impl Rng) -> u32 {
+        rng.random_range(0..4)
+    }
+
+    #[test]
+    fn compiled_expressions_match_evalexpr() {
+        let mut rng = seeded_rng(21);
+        for i in 0..2000 {
+            let kind = if i % 2 == 0 {
+                ExprKind::Number
+            } else {
+                ExprKind::Bool
+            };
+            let expr = random_expr(&mut rng, 4, kind);
+            let tree = build_operator_tree::<DefaultNumericTypes>(&expr).unwrap();
+            let compiled = CompiledExpr::compile(&tree)
+                .unwrap_or_else(|e| panic!("{} didn't compile: {}", expr, e));
+
+            for _ in 0..4 {
+                let variables = ExprVariables {
+                    levels: from_fn(|_| small(&mut rng)),
+                    received: from_fn(|_| small(&mut rng)),
+                    built_up: from_fn(|_| small(&mut rng)),
+                    ticks_since_fire: rng.random_range(0..100),
+                    time: rng.random_range(0..u64::MAX),
+                    random: rng.random_range(0..RANDOM_RANGE),
+                };
+                let expected = reference(&expr, &variables);
+                let actual = compiled.eval(&variables).ok();
+                assert!(
+                    same(expected, actual),
+                    "{} gave {:?} instead of {:?}",
+                    expr,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn type_errors_fail_like_evalexpr() {
+        let variables = ExprVariables::default().with_level::<Dopamine>(3);
+        for expr in [
+            "dopamine && true",
+            "!dopamine",
+            "-(1 < 2)",
+            "if(dopamine, 1, 2)",
+            "(1 < 2) + 1",
+            "9223372036854775807 + dopamine",
+            "dopamine / 0",
+            "math::abs(true)",
+            "min(1 < 2, 3)",
+            // The branch that isn't taken is evaluated all the same
+            "if(true, 1, dopamine % 0)",
+        ] {
+            let tree = build_operator_tree::<DefaultNumericTypes>(expr).unwrap();
+            assert!(reference(expr, &variables).is_none(), "{}", expr);
+            assert!(
+                CompiledExpr::compile(&tree)
+                    .unwrap()
+                    .eval(&variables)
+                    .is_err(),
+                "{}",
+                expr
+            );
+        }
+        // Equality across types is false rather than an error
+        let tree = build_operator_tree::<DefaultNumericTypes>("(1 == 1.0) || (true != 1)").unwrap();
+        assert_eq!(
+            CompiledExpr::compile(&tree).unwrap().eval(&variables),
+            Ok(ExprValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn only_gene_expressions_compile() {
+        for expr in [
+            "a = 1",
+            "\"text\"",
+            "unknown + 1",
+            "math::sin(1, 2)",
+            "1; 2",
+            "",
+        ] {
+            let tree = build_operator_tree::<DefaultNumericTypes>(expr).unwrap();
+            assert!(CompiledExpr::compile(&tree).is_err(), "{}", expr);
+        }
+    }
+}
@@ -1,96 +1,791 @@
+use evalexpr::{DefaultNumericTypes, Node, Operator, Value};
 use gene_traits::amino_acid::AminoAcid;
 
-pub fn expr_from_amino_acids(sequence: &[AminoAcid]) -> String {
-    sequence
-        .windows(2)
-        .enumerate()
-        // chunk-like reading: pairs at indices (0,1), (2,3), ...
-        .filter(|(idx, _)| idx % 2 == 0)
-        .map(|tpl| tpl.1)
-        .map(|acid: &[AminoAcid]| match acid {
-            &[AminoAcid::A, AminoAcid::A] => "(",
-            &[AminoAcid::A, AminoAcid::P] => ")",
-            &[AminoAcid::A, AminoAcid::F] => "*",
-            &[AminoAcid::A, AminoAcid::M] => "/",
-            &[AminoAcid::A, AminoAcid::K] => "^",
-            &[AminoAcid::A, AminoAcid::S] => "+",
-            &[AminoAcid::A, AminoAcid::W] => "-",
-            &[AminoAcid::A, AminoAcid::T] => "%",
-            &[AminoAcid::A, AminoAcid::Y] => "<",
-            &[AminoAcid::A, AminoAcid::V] => ">",
-            &[AminoAcid::A, AminoAcid::L] => "==",
-            &[AminoAcid::A, AminoAcid::H] => ">=",
-            &[AminoAcid::A, AminoAcid::D] => "<=",
-            &[AminoAcid::A, AminoAcid::N] => "!=",
-            &[AminoAcid::A, AminoAcid::R] => "!",
-            &[AminoAcid::A, AminoAcid::I] => "math::sin",
-            &[AminoAcid::A, AminoAcid::C] => "math::cos",
-            &[AminoAcid::A, AminoAcid::E] => "math::ln",
-            &[AminoAcid::A, AminoAcid::Q] => "math::log",
-            &[AminoAcid::A, AminoAcid::G] => "math::log2",
-            &[AminoAcid::P, AminoAcid::A] => "0",
-            &[AminoAcid::P, AminoAcid::P] => "1",
-            &[AminoAcid::P, AminoAcid::F] => "2",
-            &[AminoAcid::P, AminoAcid::M] => "3",
-            &[AminoAcid::P, AminoAcid::K] => "4",
-            &[AminoAcid::P, AminoAcid::S] => "5",
-            &[AminoAcid::P, AminoAcid::W] => "6",
-            &[AminoAcid::P, AminoAcid::T] => "7",
-            &[AminoAcid::P, AminoAcid::Y] => "8",
-            &[AminoAcid::P, AminoAcid::V] => "9",
-            &[AminoAcid::F, AminoAcid::A] => "dopamine",
-            &[AminoAcid::F, AminoAcid::P] => "serotonin",
-            &[AminoAcid::F, AminoAcid::F] => "norepinephrine",
-            _ => "",
+use crate::components::neurotransmitters::{
+    ACCUMULATOR_NAMES, NEUROTRANSMITTER_CODES, NEUROTRANSMITTER_COUNT, NEUROTRANSMITTER_NAMES,
+    RECEPTOR_NAMES,
+};
+
+// What an expression, or one of its operands, evaluates to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExprKind {
+    Number,
+    Bool,
+}
+
+// How a symbol is written and how many operands it reads after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    // A variable or a digit
+    Leaf,
+    // A number between 0 and 399 spelled by the pair that follows
+    Number,
+    // Written in front of its single operand
+    Prefix,
+    // Written between its two operands
+    Infix,
+    // A function call taking this many arguments
+    Function(usize),
+    // if(condition, then, otherwise), the condition is a Bool and the branches are operands
+    Conditional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub text: &'static str,
+    pub form: Form,
+    pub operands: ExprKind,
+    pub result: ExprKind,
+}
+
+impl Symbol {
+    const fn new(text: &'static str, form: Form, operands: ExprKind, result: ExprKind) -> Self {
+        Self {
+            text,
+            form,
+            operands,
+            result,
+        }
+    }
+
+    pub const fn arity(&self) -> usize {
+        match self.form {
+            Form::Leaf | Form::Number => 0,
+            Form::Prefix => 1,
+            Form::Infix => 2,
+            Form::Function(arity) => arity,
+            Form::Conditional => 3,
+        }
+    }
+
+    // What the operand at the given position has to evaluate to
+    pub const fn operand(&self, index: usize) -> ExprKind {
+        match self.form {
+            Form::Conditional if index == 0 => ExprKind::Bool,
+            _ => self.operands,
+        }
+    }
+}
+
+const fn leaf(text: &'static str) -> Symbol {
+    Symbol::new(text, Form::Leaf, ExprKind::Number, ExprKind::Number)
+}
+
+const fn arithmetic(text: &'static str) -> Symbol {
+    Symbol::new(text, Form::Infix, ExprKind::Number, ExprKind::Number)
+}
+
+const fn comparison(text: &'static str) -> Symbol {
+    Symbol::new(text, Form::Infix, ExprKind::Number, ExprKind::Bool)
+}
+
+const fn logic(text: &'static str) -> Symbol {
+    Symbol::new(text, Form::Infix, ExprKind::Bool, ExprKind::Bool)
+}
+
+const fn function(text: &'static str, arity: usize) -> Symbol {
+    Symbol::new(
+        text,
+        Form::Function(arity),
+        ExprKind::Number,
+        ExprKind::Number,
+    )
+}
+
+// The symbols that don't belong to a neurotransmitter
+const SHARED_SYMBOLS: [([AminoAcid; 2], Symbol); 42] = [
+    ([AminoAcid::A, AminoAcid::F], arithmetic("*")),
+    ([AminoAcid::A, AminoAcid::M], arithmetic("/")),
+    ([AminoAcid::A, AminoAcid::K], arithmetic("^")),
+    ([AminoAcid::A, AminoAcid::S], arithmetic("+")),
+    ([AminoAcid::A, AminoAcid::W], arithmetic("-")),
+    ([AminoAcid::A, AminoAcid::T], arithmetic("%")),
+    ([AminoAcid::A, AminoAcid::Y], comparison("<")),
+    ([AminoAcid::A, AminoAcid::V], comparison(">")),
+    ([AminoAcid::A, AminoAcid::L], comparison("==")),
+    ([AminoAcid::A, AminoAcid::H], comparison(">=")),
+    ([AminoAcid::A, AminoAcid::D], comparison("<=")),
+    ([AminoAcid::A, AminoAcid::N], comparison("!=")),
+    (
+        [AminoAcid::A, AminoAcid::R],
+        Symbol::new("!", Form::Prefix, ExprKind::Bool, ExprKind::Bool),
+    ),
+    (
+        [AminoAcid::P, AminoAcid::N],
+        Symbol::new("-", Form::Prefix, ExprKind::Number, ExprKind::Number),
+    ),
+    ([AminoAcid::A, AminoAcid::I], function("math::sin", 1)),
+    ([AminoAcid::A, AminoAcid::C], function("math::cos", 1)),
+    ([AminoAcid::A, AminoAcid::E], function("math::ln", 1)),
+    ([AminoAcid::A, AminoAcid::Q], function("math::log", 2)),
+    ([AminoAcid::A, AminoAcid::G], function("math::log2", 1)),
+    ([AminoAcid::P, AminoAcid::A], leaf("0")),
+    ([AminoAcid::P, AminoAcid::P], leaf("1")),
+    ([AminoAcid::P, AminoAcid::F], leaf("2")),
+    ([AminoAcid::P, AminoAcid::M], leaf("3")),
+    ([AminoAcid::P, AminoAcid::K], leaf("4")),
+    ([AminoAcid::P, AminoAcid::S], leaf("5")),
+    ([AminoAcid::P, AminoAcid::W], leaf("6")),
+    ([AminoAcid::P, AminoAcid::T], leaf("7")),
+    ([AminoAcid::P, AminoAcid::Y], leaf("8")),
+    ([AminoAcid::P, AminoAcid::V], leaf("9")),
+    (
+        [AminoAcid::P, AminoAcid::D],
+        Symbol::new("", Form::Number, ExprKind::Number, ExprKind::Number),
+    ),
+    ([AminoAcid::F, AminoAcid::V], leaf("ticks_since_fire")),
+    ([AminoAcid::F, AminoAcid::D], leaf("time")),
+    ([AminoAcid::F, AminoAcid::N], leaf("random")),
+    ([AminoAcid::C, AminoAcid::A], function("min", 2)),
+    ([AminoAcid::C, AminoAcid::P], function("max", 2)),
+    ([AminoAcid::C, AminoAcid::F], function("clamp", 3)),
+    (
+        [AminoAcid::C, AminoAcid::M],
+        Symbol::new("if", Form::Conditional, ExprKind::Number, ExprKind::Number),
+    ),
+    ([AminoAcid::C, AminoAcid::K], function("sigmoid", 1)),
+    ([AminoAcid::C, AminoAcid::S], function("math::tanh", 1)),
+    ([AminoAcid::C, AminoAcid::W], function("math::abs", 1)),
+    ([AminoAcid::C, AminoAcid::Y], logic("&&")),
+    ([AminoAcid::C, AminoAcid::V], logic("||")),
+];
+
+const SYMBOL_COUNT: usize = SHARED_SYMBOLS.len() + 3 * NEUROTRANSMITTER_COUNT;
+
+/**
+ * Every amino acid pair an expression gene understands.  Expressions are written in prefix order,
+ * each symbol followed by its operands, so no sequence of pairs can be unbalanced.  Pairs that
+ * aren't listed here are skipped.  A new symbol gives meaning to a pair that used to be skipped,
+ * which changes how existing genes decode, so adding one means bumping the version of every
+ * expression gene, see SYMBOLS_PINS.  Each neurotransmitter brings the codes of its own variables.
+ */
+pub const EXPR_SYMBOLS: [([AminoAcid; 2], Symbol); SYMBOL_COUNT] = {
+    let mut symbols = [SHARED_SYMBOLS[0]; SYMBOL_COUNT];
+    let mut i = 0;
+    while i < SHARED_SYMBOLS.len() {
+        symbols[i] = SHARED_SYMBOLS[i];
+        i += 1;
+    }
+    let mut n = 0;
+    while n < NEUROTRANSMITTER_COUNT {
+        let [level, receptor, accumulator] = NEUROTRANSMITTER_CODES[n];
+        symbols[i] = (level, leaf(NEUROTRANSMITTER_NAMES[n]));
+        symbols[i + 1] = (receptor, leaf(RECEPTOR_NAMES[n]));
+        symbols[i + 2] = (accumulator, leaf(ACCUMULATOR_NAMES[n]));
+        i += 3;
+        n += 1;
+    }
+    symbols
+};
+
+// A fingerprint of EXPR_SYMBOLS and the versions the expression genes were given for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolsPin {
+    pub fingerprint: u64,
+    pub activation: u32,
+    pub update_function: u32,
+}
+
+/**
+ * Every symbol table the expression genes have been versioned for, the last one is the current
+ * table.  A test compares it with the table and the gene versions.  When the table changes, bump
+ * the version of every expression gene and add a pin with the new fingerprint and versions, the
+ * versions of each pin have to be higher than those of the one before.
+ */
+pub const SYMBOLS_PINS: &[SymbolsPin] = &[SymbolsPin {
+    fingerprint: 0x2571_d6eb_3b71_606c,
+    activation: 2,
+    update_function: 3,
+}];
+
+// 64 bit FNV-1a over the table as Debug writes it, codes, order and symbols included
+pub fn symbols_fingerprint() -> u64 {
+    format!("{:?}", EXPR_SYMBOLS)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
         })
-        .collect()
 }
 
-// Determine the slice end before the terminator/promoter marker
-pub fn last_idx_before_promoter(sequence: &[AminoAcid], promoter_size: usize) -> usize {
-    if let Some(last_idx) = sequence
-        .windows(promoter_size)
-        .position(|w| w.eq(&[AminoAcid::UNKNOWN; 4]))
-    {
-        last_idx
+// The largest number a single Number symbol spells
+const NUMBER_MAX: u32 = 399;
+
+fn symbol_for(pair: &[AminoAcid]) -> Option<Symbol> {
+    EXPR_SYMBOLS
+        .iter()
+        .find(|(code, _)| code == pair)
+        .map(|(_, symbol)| *symbol)
+}
+
+fn find_symbol(matches: impl Fn(&Symbol) -> bool) -> Option<&'static ([AminoAcid; 2], Symbol)> {
+    EXPR_SYMBOLS.iter().find(|(_, symbol)| matches(symbol))
+}
+
+fn code_for(matches: impl Fn(&Symbol) -> bool) -> Option<[AminoAcid; 2]> {
+    find_symbol(matches).map(|(code, _)| *code)
+}
+
+// A decoded piece of expression, atoms can be used as operands without parentheses
+struct Decoded {
+    text: String,
+    atom: bool,
+}
+
+impl Decoded {
+    fn operand(&self) -> String {
+        if self.atom {
+            self.text.clone()
+        } else {
+            format!("({})", self.text)
+        }
+    }
+
+    // Makes the piece fit where the other kind was expected.  Numbers are true when positive.
+    fn coerce(self, from: ExprKind, to: ExprKind) -> Decoded {
+        match (from, to) {
+            (ExprKind::Number, ExprKind::Bool) => Decoded {
+                text: format!("{}>0", self.operand()),
+                atom: false,
+            },
+            (ExprKind::Bool, ExprKind::Number) => Decoded {
+                text: format!("if({},1,0)", self.text),
+                atom: true,
+            },
+            _ => self,
+        }
+    }
+}
+
+struct ExprDecoder<'a> {
+    pairs: std::slice::ChunksExact<'a, AminoAcid>,
+}
+
+impl ExprDecoder<'_> {
+    fn next_symbol(&mut self) -> Option<Symbol> {
+        self.pairs.by_ref().find_map(symbol_for)
+    }
+
+    // Acids without a value count as 0, as does a number cut off by the end of the gene
+    fn next_number(&mut self) -> u32 {
+        let value = |acid: AminoAcid| u8::try_from(acid).map_or(0, u32::from);
+        self.pairs
+            .next()
+            .map_or(0, |pair| value(pair[0]) * 20 + value(pair[1]))
+    }
+
+    fn decode(&mut self, expected: ExprKind) -> Decoded {
+        // Operands missing at the end of the gene are filled in
+        let Some(symbol) = self.next_symbol() else {
+            let text = match expected {
+                ExprKind::Number => "0",
+                ExprKind::Bool => "false",
+            };
+            return Decoded {
+                text: text.to_string(),
+                atom: true,
+            };
+        };
+        let decoded = match symbol.form {
+            Form::Leaf => Decoded {
+                text: symbol.text.to_string(),
+                atom: true,
+            },
+            Form::Number => Decoded {
+                text: self.next_number().to_string(),
+                atom: true,
+            },
+            Form::Prefix => Decoded {
+                text: format!("{}{}", symbol.text, self.decode(symbol.operands).operand()),
+                atom: false,
+            },
+            Form::Infix => {
+                let left = self.decode(symbol.operands).operand();
+                let right = self.decode(symbol.operands).operand();
+                Decoded {
+                    text: format!("{}{}{}", left, symbol.text, right),
+                    atom: false,
+                }
+            }
+            Form::Function(_) | Form::Conditional => {
+                let arguments: Vec<String> = (0..symbol.arity())
+                    .map(|index| self.decode(symbol.operand(index)).text)
+                    .collect();
+                Decoded {
+                    text: format!("{}({})", symbol.text, arguments.join(",")),
+                    atom: true,
+                }
+            }
+        };
+        decoded.coerce(symbol.result, expected)
+    }
+}
+
+/**
+ * Reads the pairs of a gene as a prefix expression of the given kind.  Every sequence decodes to a
+ * well formed expression: unknown pairs are skipped, missing operands are filled in with 0 or
+ * false, operands of the wrong kind are converted and whatever follows the first complete
+ * expression is ignored.
+ */
+pub fn expr_from_amino_acids(sequence: &[AminoAcid], kind: ExprKind) -> String {
+    ExprDecoder {
+        pairs: sequence.chunks_exact(2),
+    }
+    .decode(kind)
+    .text
+}
+
+/**
+ * Reads the pairs of an update gene as a list of assignments.  Each assignment is the pair of one of
+ * the targets followed by the expression assigned to it, any other pair met while looking for a
+ * target is skipped.  Assignments are written the way evalexpr chains them, e.g.
+ * "dopamine=dopamine+1; serotonin=0".
+ */
+pub fn assignments_from_amino_acids(sequence: &[AminoAcid], targets: &[&str]) -> String {
+    let mut decoder = ExprDecoder {
+        pairs: sequence.chunks_exact(2),
+    };
+    let mut assignments = Vec::new();
+    while let Some(symbol) = decoder.next_symbol() {
+        if symbol.form == Form::Leaf && targets.contains(&symbol.text) {
+            let value = decoder.decode(ExprKind::Number).text;
+            assignments.push(format!("{}={}", symbol.text, value));
+        }
+    }
+    assignments.join("; ")
+}
+
+// The operator written between the two operands of a binary node
+fn binary_token(operator: &Operator<DefaultNumericTypes>) -> Option<&'static str> {
+    Some(match operator {
+        Operator::Add => "+",
+        Operator::Sub => "-",
+        Operator::Mul => "*",
+        Operator::Div => "/",
+        Operator::Mod => "%",
+        Operator::Exp => "^",
+        Operator::Eq => "==",
+        Operator::Neq => "!=",
+        Operator::Gt => ">",
+        Operator::Lt => "<",
+        Operator::Geq => ">=",
+        Operator::Leq => "<=",
+        Operator::And => "&&",
+        Operator::Or => "||",
+        _ => return None,
+    })
+}
+
+// Root nodes only group their child
+fn unwrap_root(node: &Node<DefaultNumericTypes>) -> &Node<DefaultNumericTypes> {
+    match (node.operator(), node.children()) {
+        (Operator::RootNode, [child]) => unwrap_root(child),
+        _ => node,
+    }
+}
+
+fn kind_of(node: &Node<DefaultNumericTypes>) -> Option<ExprKind> {
+    let node = unwrap_root(node);
+    match node.operator() {
+        Operator::Const {
+            value: Value::Boolean(_),
+        }
+        | Operator::Not => Some(ExprKind::Bool),
+        Operator::Const {
+            value: Value::Int(_) | Value::Float(_),
+        }
+        | Operator::VariableIdentifierRead { .. }
+        | Operator::FunctionIdentifier { .. }
+        | Operator::Neg => Some(ExprKind::Number),
+        operator => binary_token(operator)
+            .and_then(|token| find_symbol(|s| s.form == Form::Infix && s.text == token))
+            .map(|(_, symbol)| symbol.result),
+    }
+}
+
+fn push_code(out: &mut Vec<AminoAcid>, code: [AminoAcid; 2]) {
+    out.extend_from_slice(&code);
+}
+
+fn number_code() -> [AminoAcid; 2] {
+    code_for(|s| s.form == Form::Number).expect("Number symbol")
+}
+
+// Digits are single symbols, bigger numbers are spelled in base 399
+fn encode_int(value: u64, out: &mut Vec<AminoAcid>) {
+    let acid = |value: u64| AminoAcid::try_from(value as u8).expect("Below 20");
+    if value <= 9 {
+        let digit = value.to_string();
+        push_code(
+            out,
+            code_for(|s| s.form == Form::Leaf && s.text == digit).expect("Digit symbol"),
+        );
+    } else if value <= u64::from(NUMBER_MAX) {
+        push_code(out, number_code());
+        out.extend_from_slice(&[acid(value / 20), acid(value % 20)]);
     } else {
-        sequence.len() - 1
+        let (quotient, remainder) = (value / 399, value % 399);
+        if remainder != 0 {
+            push_code(out, code_for(|s| s.text == "+").expect("Addition"));
+        }
+        push_code(out, code_for(|s| s.text == "*").expect("Multiplication"));
+        encode_int(u64::from(NUMBER_MAX), out);
+        encode_int(quotient, out);
+        if remainder != 0 {
+            encode_int(remainder, out);
+        }
+    }
+}
+
+// The single argument of a call, or the members of the tuple a call with several takes
+fn arguments(node: &Node<DefaultNumericTypes>) -> Vec<&Node<DefaultNumericTypes>> {
+    let argument = unwrap_root(node);
+    match argument.operator() {
+        Operator::Tuple => argument.children().iter().collect(),
+        _ => vec![argument],
+    }
+}
+
+fn is_int(node: &Node<DefaultNumericTypes>, expected: i64) -> bool {
+    matches!(unwrap_root(node).operator(), Operator::Const { value: Value::Int(value) } if *value == expected)
+}
+
+fn encode(
+    node: &Node<DefaultNumericTypes>,
+    expected: ExprKind,
+    out: &mut Vec<AminoAcid>,
+) -> Result<(), String> {
+    let node = unwrap_root(node);
+    let operator = node.operator();
+
+    // What the decoder writes when converting between kinds is spelled as the conversion, so that
+    // encoding a decoded gene gives back the same gene
+    match (expected, operator, node.children()) {
+        (
+            ExprKind::Bool,
+            Operator::Const {
+                value: Value::Boolean(value),
+            },
+            [],
+        ) => {
+            encode_int(u64::from(*value), out);
+            return Ok(());
+        }
+        (ExprKind::Bool, Operator::Gt, [operand, zero])
+            if is_int(zero, 0) && kind_of(operand) == Some(ExprKind::Number) =>
+        {
+            return encode(operand, ExprKind::Number, out);
+        }
+        (ExprKind::Number, Operator::FunctionIdentifier { identifier }, [argument])
+            if identifier == "if" =>
+        {
+            if let [condition, one, zero] = arguments(argument)[..]
+                && is_int(one, 1)
+                && is_int(zero, 0)
+                && kind_of(condition) == Some(ExprKind::Bool)
+            {
+                return encode(condition, ExprKind::Bool, out);
+            }
+        }
+        _ => {}
+    }
+
+    let kind = kind_of(node);
+    if kind != Some(expected) {
+        return Err(format!("{} is not a {:?} expression", node, expected));
+    }
+    match (operator, node.children()) {
+        (
+            Operator::Const {
+                value: Value::Int(value),
+            },
+            [],
+        ) => {
+            if *value < 0 {
+                push_code(
+                    out,
+                    code_for(|s| s.form == Form::Prefix && s.text == "-").expect("Negation"),
+                );
+            }
+            encode_int(value.unsigned_abs(), out);
+        }
+        (Operator::VariableIdentifierRead { identifier }, []) => push_code(
+            out,
+            code_for(|s| s.form == Form::Leaf && s.text == identifier)
+                .ok_or_else(|| format!("no gene reads the variable {}", identifier))?,
+        ),
+        (Operator::Neg | Operator::Not, [operand]) => {
+            let token = if *operator == Operator::Neg { "-" } else { "!" };
+            let (code, symbol) =
+                find_symbol(|s| s.form == Form::Prefix && s.text == token).expect("Prefix");
+            push_code(out, *code);
+            encode(operand, symbol.operands, out)?;
+        }
+        (Operator::FunctionIdentifier { identifier }, [argument]) => {
+            let arguments = arguments(argument);
+            let (code, symbol) = find_symbol(|s| {
+                s.text == identifier
+                    && matches!(s.form, Form::Function(_) | Form::Conditional)
+                    && s.arity() == arguments.len()
+            })
+            .ok_or_else(|| {
+                format!(
+                    "no gene calls {} with {} arguments",
+                    identifier,
+                    arguments.len()
+                )
+            })?;
+            push_code(out, *code);
+            for (index, argument) in arguments.into_iter().enumerate() {
+                encode(argument, symbol.operand(index), out)?;
+            }
+        }
+        (_, [left, right]) if binary_token(operator).is_some() => {
+            let token = binary_token(operator).expect("Checked above");
+            let (code, symbol) =
+                find_symbol(|s| s.form == Form::Infix && s.text == token).expect("Infix");
+            push_code(out, *code);
+            encode(left, symbol.operands, out)?;
+            encode(right, symbol.operands, out)?;
+        }
+        _ => return Err(format!("no gene spells {}", node)),
+    }
+    Ok(())
+}
+
+/**
+ * The reverse of expr_from_amino_acids.  Parsing what a gene decodes to and encoding it again gives
+ * back the same pairs, anything that can't be spelled, like fractions or unknown variables, is an
+ * error.
+ */
+pub fn amino_acids_from_node(
+    node: &Node<DefaultNumericTypes>,
+    kind: ExprKind,
+) -> Result<Vec<AminoAcid>, String> {
+    let mut acids = Vec::new();
+    encode(node, kind, &mut acids)?;
+    Ok(acids)
+}
+
+pub fn amino_acids_from_expr(expr: &str, kind: ExprKind) -> Result<Vec<AminoAcid>, String> {
+    let node =
+        evalexpr::build_operator_tree::<DefaultNumericTypes>(expr).map_err(|e| format!("{}", e))?;
+    amino_acids_from_node(&node, kind)
+}
+
+/**
+ * Splits a chain of assignments like "dopamine = dopamine + 1; serotonin = 0" into the variables
+ * assigned and their values, in order.  Empty statements are skipped, anything else that isn't an
+ * assignment is an error.
+ */
+pub fn assignments(
+    node: &Node<DefaultNumericTypes>,
+) -> Result<Vec<(&str, &Node<DefaultNumericTypes>)>, String> {
+    let node = unwrap_root(node);
+    let statements = match node.operator() {
+        Operator::Chain => node.children(),
+        _ => std::slice::from_ref(node),
+    };
+    statements
+        .iter()
+        .map(unwrap_root)
+        .filter(|statement| {
+            !(statement.operator() == &Operator::RootNode && statement.children().is_empty())
+        })
+        .map(
+            |statement| match (statement.operator(), statement.children()) {
+                (Operator::Assign, [target, value]) => match unwrap_root(target).operator() {
+                    Operator::VariableIdentifierWrite { identifier } => {
+                        Ok((identifier.as_str(), value))
+                    }
+                    _ => Err(format!("{} doesn't assign to a variable", statement)),
+                },
+                _ => Err(format!("{} isn't an assignment", statement)),
+            },
+        )
+        .collect()
+}
+
+// The reverse of assignments_from_amino_acids, only the given targets can be assigned
+pub fn amino_acids_from_assignments(
+    node: &Node<DefaultNumericTypes>,
+    targets: &[&str],
+) -> Result<Vec<AminoAcid>, String> {
+    let mut acids = Vec::new();
+    for (target, value) in assignments(node)? {
+        if !targets.contains(&target) {
+            return Err(format!("genes can't assign to {}", target));
+        }
+        push_code(
+            &mut acids,
+            code_for(|s| s.form == Form::Leaf && s.text == target).expect("Targets are variables"),
+        );
+        encode(value, ExprKind::Number, &mut acids)?;
+    }
+    Ok(acids)
+}
+
+pub fn amino_acids_from_assignment_expr(
+    expr: &str,
+    targets: &[&str],
+) -> Result<Vec<AminoAcid>, String> {
+    let node =
+        evalexpr::build_operator_tree::<DefaultNumericTypes>(expr).map_err(|e| format!("{}", e))?;
+    amino_acids_from_assignments(&node, targets)
+}
+
+// Determine the slice end before the stop codon.  Without a stop the gene runs to the end of the
+// sequence.
+pub fn last_idx_before_stop(sequence: &[AminoAcid]) -> usize {
+    sequence
+        .iter()
+        .position(|acid| *acid == AminoAcid::STOP)
+        .unwrap_or(sequence.len())
+}
+
+// How much of the sequence a gene ending at last_idx uses up, the stop codon included
+pub fn consumed_through_stop(sequence: &[AminoAcid], last_idx: usize) -> usize {
+    (last_idx + 1).min(sequence.len())
+}
+
+// Random well typed expressions over the symbols genes can spell, for property tests of the
+// expression genes
+#[cfg(test)]
+pub(crate) fn random_expr<R: rand::Rng>(rng: &mut R, depth: usize, kind: ExprKind) -> String {
+    use crate::components::expr_variables::ExprVariables;
+    const ARITHMETIC: [&str; 6] = ["+", "-", "*", "/", "%", "^"];
+    const COMPARISONS: [&str; 6] = ["==", "!=", "<", ">", "<=", ">="];
+    const FUNCTIONS: [&str; 7] = [
+        "math::sin",
+        "math::cos",
+        "math::ln",
+        "math::log2",
+        "sigmoid",
+        "math::tanh",
+        "math::abs",
+    ];
+    const LOGIC: [&str; 2] = ["&&", "||"];
+
+    let pick = |rng: &mut R, options: &[&'static str]| options[rng.random_range(0..options.len())];
+    let number = |rng: &mut R| random_expr(rng, depth.saturating_sub(1), ExprKind::Number);
+    match kind {
+        ExprKind::Bool if depth == 0 => pick(rng, &["true", "false"]).to_string(),
+        ExprKind::Bool => match rng.random_range(0..4) {
+            0 => format!("!({})", random_expr(rng, depth - 1, ExprKind::Bool)),
+            1 => format!(
+                "({}){}({})",
+                random_expr(rng, depth - 1, ExprKind::Bool),
+                pick(rng, &LOGIC),
+                random_expr(rng, depth - 1, ExprKind::Bool)
+            ),
+            // Left without parentheses, so precedence has to survive the round trip too
+            2 => format!("{}{}{}", number(rng), pick(rng, &COMPARISONS), number(rng)),
+            _ => format!(
+                "({}){}({})",
+                number(rng),
+                pick(rng, &COMPARISONS),
+                number(rng)
+            ),
+        },
+        ExprKind::Number if depth == 0 || rng.random_bool(0.3) => match rng.random_range(0..3) {
+            0 => pick(rng, &ExprVariables::NAMES).to_string(),
+            1 => rng.random_range(0..1000).to_string(),
+            _ => rng.random_range(0..1_000_000).to_string(),
+        },
+        ExprKind::Number => match rng.random_range(0..8) {
+            0 => format!("-{}", number(rng)),
+            1 => format!("{}({})", pick(rng, &FUNCTIONS), number(rng)),
+            2 => format!(
+                "{}({}, {})",
+                pick(rng, &["math::log", "min", "max"]),
+                number(rng),
+                number(rng)
+            ),
+            3 => format!("clamp({}, {}, {})", number(rng), number(rng), number(rng)),
+            4 => format!(
+                "if({}, {}, {})",
+                random_expr(rng, depth - 1, ExprKind::Bool),
+                number(rng),
+                number(rng)
+            ),
+            5 => format!("{}{}{}", number(rng), pick(rng, &ARITHMETIC), number(rng)),
+            _ => format!(
+                "({}){}({})",
+                number(rng),
+                pick(rng, &ARITHMETIC),
+                number(rng)
+            ),
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{expr_from_amino_acids, last_idx_before_promoter};
-    use evalexpr::{build_operator_tree, context_map, DefaultNumericTypes};
-    use gene_traits::amino_acid::AminoAcid;
+    use super::{
+        EXPR_SYMBOLS, ExprKind, SYMBOLS_PINS, SymbolsPin, amino_acids_from_assignment_expr,
+        amino_acids_from_expr, amino_acids_from_node, assignments_from_amino_acids,
+        consumed_through_stop, expr_from_amino_acids, last_idx_before_stop, random_expr,
+        symbols_fingerprint,
+    };
+    use crate::components::expr_variables::{ExprVariables, RANDOM_RANGE};
+    use crate::components::{Activation, UpdateFunction};
+    use evalexpr::{DefaultNumericTypes, build_operator_tree, context_map};
+    use gene_traits::{amino_acid::AminoAcid, header::Gene, mutation::seeded_rng};
+    use rand::Rng;
+    use std::array::from_fn;
+
+    #[test]
+    fn expression_gene_versions_follow_the_symbols() {
+        let current = SymbolsPin {
+            fingerprint: symbols_fingerprint(),
+            activation: Activation::ID.version,
+            update_function: UpdateFunction::ID.version,
+        };
+        let pinned = SYMBOLS_PINS.last().unwrap();
+        assert_eq!(
+            current.fingerprint, pinned.fingerprint,
+            "EXPR_SYMBOLS changed, bump the version of every expression gene and pin {:#x} with them",
+            current.fingerprint
+        );
+        assert_eq!(
+            current, *pinned,
+            "the last pin has to name the versions the expression genes have"
+        );
+        for pins in SYMBOLS_PINS.windows(2) {
+            assert!(
+                pins[1].activation > pins[0].activation
+                    && pins[1].update_function > pins[0].update_function,
+                "{:#x} was pinned without bumping every expression gene",
+                pins[1].fingerprint
+            );
+        }
+    }
 
     #[test]
     fn expr_parser_maps_pairs_to_expected_expression() {
-        // Target expression: 2*dopamine - 3*serotonin + norepinephrine
-        // Encoded as amino acids: PFAFFAAWPMAFFPASFF
+        // Target expression: 2*dopamine - 3*serotonin + norepinephrine, in prefix order
         let amino_acids = [
-            AminoAcid::P,
-            AminoAcid::F, // "2"
+            AminoAcid::A,
+            AminoAcid::S, // "+"
+            AminoAcid::A,
+            AminoAcid::W, // "-"
             AminoAcid::A,
             AminoAcid::F, // "*"
+            AminoAcid::P,
+            AminoAcid::F, // "2"
             AminoAcid::F,
             AminoAcid::A, // "dopamine"
             AminoAcid::A,
-            AminoAcid::W, // "-"
+            AminoAcid::F, // "*"
             AminoAcid::P,
             AminoAcid::M, // "3"
-            AminoAcid::A,
-            AminoAcid::F, // "*"
             AminoAcid::F,
             AminoAcid::P, // "serotonin"
-            AminoAcid::A,
-            AminoAcid::S, // "+"
             AminoAcid::F,
             AminoAcid::F, // "norepinephrine"
         ];
 
-        let expected = "2*dopamine-3*serotonin+norepinephrine";
-        let actual = expr_from_amino_acids(&amino_acids);
+        let expected = "((2*dopamine)-(3*serotonin))+norepinephrine";
+        let actual = expr_from_amino_acids(&amino_acids, ExprKind::Number);
         assert_eq!(expected, actual);
 
         // Ensure the expression compiles and evaluates correctly with a context
@@ -106,31 +801,238 @@ mod tests {
     }
 
     #[test]
-    fn last_index_detects_promoter_window() {
-        // Data (6) + promoter (4 UNKNOWN) + trailing data
+    fn every_sequence_decodes_to_an_expression() {
+        let mut rng = seeded_rng(18);
+        let acids = [AminoAcid::START, AminoAcid::UNKNOWN]
+            .into_iter()
+            .chain((0..20).map(|value| AminoAcid::try_from(value).unwrap()))
+            .collect::<Vec<_>>();
+        for _ in 0..2000 {
+            let length = rng.random_range(0..40);
+            let sequence: Vec<_> = (0..length)
+                .map(|_| acids[rng.random_range(0..acids.len())])
+                .collect();
+            for kind in [ExprKind::Number, ExprKind::Bool] {
+                let expr = expr_from_amino_acids(&sequence, kind);
+                let tree = build_operator_tree::<DefaultNumericTypes>(&expr)
+                    .unwrap_or_else(|e| panic!("{:?} decoded to {}: {}", sequence, expr, e));
+                // Decoded expressions always encode again
+                amino_acids_from_node(&tree, kind).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn short_and_odd_genes_are_completed() {
+        assert_eq!(expr_from_amino_acids(&[], ExprKind::Number), "0");
+        assert_eq!(expr_from_amino_acids(&[], ExprKind::Bool), "false");
+        // A comparison missing its second operand, then a stray acid
+        let acids = [
+            AminoAcid::A,
+            AminoAcid::H,
+            AminoAcid::F,
+            AminoAcid::A,
+            AminoAcid::W,
+        ];
+        assert_eq!(expr_from_amino_acids(&acids, ExprKind::Bool), "dopamine>=0");
+        // Numbers read as booleans when they are positive, and the other way around
+        assert_eq!(
+            expr_from_amino_acids(&[AminoAcid::F, AminoAcid::A], ExprKind::Bool),
+            "dopamine>0"
+        );
+        assert_eq!(
+            expr_from_amino_acids(&acids[..4], ExprKind::Number),
+            "if(dopamine>=0,1,0)"
+        );
+        // Unlisted pairs are skipped
+        assert_eq!(
+            expr_from_amino_acids(
+                &[AminoAcid::A, AminoAcid::A, AminoAcid::P, AminoAcid::V],
+                ExprKind::Number
+            ),
+            "9"
+        );
+    }
+
+    #[test]
+    fn symbols_have_distinct_codes() {
+        for (i, (code, _)) in EXPR_SYMBOLS.iter().enumerate() {
+            assert!(EXPR_SYMBOLS[i + 1..].iter().all(|(other, _)| other != code));
+            assert!(code.iter().all(|acid| u8::try_from(*acid).is_ok()));
+        }
+    }
+
+    #[test]
+    fn expr_encoder_round_trips() {
+        let expr = "(dopamine + 12) >= math::log2(serotonin) * 3";
+        let acids = amino_acids_from_expr(expr, ExprKind::Bool).unwrap();
+
+        assert_eq!(
+            expr_from_amino_acids(&acids, ExprKind::Bool),
+            "(dopamine+12)>=(math::log2(serotonin)*3)"
+        );
+        // Numbers above 399 are spelled in base 399
+        let acids = amino_acids_from_expr("-1234567", ExprKind::Number).unwrap();
+        assert_eq!(
+            expr_from_amino_acids(&acids, ExprKind::Number),
+            "-((399*((399*7)+301))+61)"
+        );
+        // The conditional takes a condition and two numbers
+        let acids = amino_acids_from_expr(
+            "if(random < 10 || ticks_since_fire > 9, sigmoid(dopamine), clamp(time, 0, 5))",
+            ExprKind::Number,
+        )
+        .unwrap();
+        assert_eq!(
+            expr_from_amino_acids(&acids, ExprKind::Number),
+            "if((random<10)||(ticks_since_fire>9),sigmoid(dopamine),clamp(time,0,5))"
+        );
+        assert!(amino_acids_from_expr("dopamine = 1", ExprKind::Number).is_err());
+        assert!(amino_acids_from_expr("dopamine", ExprKind::Number).is_ok());
+    }
+
+    #[test]
+    fn assignments_round_trip() {
+        const TARGETS: [&str; 2] = ["dopamine", "serotonin"];
+        let acids = amino_acids_from_assignment_expr(
+            "dopamine = dopamine * 9 / 10; serotonin = serotonin + 1;",
+            &TARGETS,
+        )
+        .unwrap();
+        assert_eq!(
+            assignments_from_amino_acids(&acids, &TARGETS),
+            "dopamine=(dopamine*9)/10; serotonin=serotonin+1"
+        );
+        assert_eq!(amino_acids_from_assignment_expr("", &TARGETS), Ok(vec![]));
+
+        // Pairs other than targets are skipped between assignments, and a target cut off by the
+        // end of the gene is assigned 0
+        let acids = [
+            AminoAcid::P,
+            AminoAcid::M, // "3", not a target
+            AminoAcid::F,
+            AminoAcid::P, // "serotonin ="
+            AminoAcid::P,
+            AminoAcid::M, // "3"
+            AminoAcid::F,
+            AminoAcid::F, // "norepinephrine", not a target
+            AminoAcid::F,
+            AminoAcid::A, // "dopamine ="
+        ];
+        assert_eq!(
+            assignments_from_amino_acids(&acids, &TARGETS),
+            "serotonin=3; dopamine=0"
+        );
+
+        for unassignable in ["norepinephrine = 1", "dopamine + 1", "dopamine = (1 < 2)"] {
+            assert!(
+                amino_acids_from_assignment_expr(unassignable, &TARGETS).is_err(),
+                "{}",
+                unassignable
+            );
+        }
+    }
+
+    #[test]
+    fn unspellable_trees_are_refused() {
+        for (unspellable, kind) in [
+            ("1.5", ExprKind::Number),
+            ("dopamine && serotonin", ExprKind::Bool),
+            ("a = 1", ExprKind::Number),
+            ("unknown", ExprKind::Number),
+            ("1 + (2 < 3)", ExprKind::Number),
+            ("dopamine", ExprKind::Bool),
+        ] {
+            let tree = build_operator_tree::<DefaultNumericTypes>(unspellable).unwrap();
+            assert!(
+                amino_acids_from_node(&tree, kind).is_err(),
+                "{}",
+                unspellable
+            );
+        }
+    }
+
+    fn random_variables(rng: &mut impl Rng) -> ExprVariables {
+        ExprVariables {
+            levels: from_fn(|_| rng.random_range(0..100)),
+            received: from_fn(|_| rng.random_range(0..100)),
+            built_up: from_fn(|_| rng.random_range(0..100)),
+            ticks_since_fire: rng.random_range(0..100),
+            time: rng.random_range(0..10_000),
+            random: rng.random_range(0..RANDOM_RANGE),
+        }
+    }
+
+    #[test]
+    fn every_variable_has_a_code() {
+        for name in ExprVariables::NAMES {
+            let acids = amino_acids_from_expr(name, ExprKind::Number).unwrap();
+            assert_eq!(acids.len(), 2);
+            assert_eq!(expr_from_amino_acids(&acids, ExprKind::Number), name);
+        }
+    }
+
+    #[test]
+    fn encoded_trees_evaluate_the_same() {
+        let mut rng = seeded_rng(16);
+        for i in 0..500 {
+            let kind = if i % 2 == 0 {
+                ExprKind::Number
+            } else {
+                ExprKind::Bool
+            };
+            let expr = random_expr(&mut rng, 4, kind);
+            let tree = build_operator_tree::<DefaultNumericTypes>(&expr).unwrap();
+            let acids = amino_acids_from_node(&tree, kind)
+                .unwrap_or_else(|e| panic!("{} didn't encode: {}", expr, e));
+            let decoded =
+                build_operator_tree::<DefaultNumericTypes>(&expr_from_amino_acids(&acids, kind))
+                    .unwrap_or_else(|e| panic!("{} didn't read back: {}", expr, e));
+
+            // Encoding the decoded tree is already canonical
+            assert_eq!(
+                amino_acids_from_node(&decoded, kind).unwrap(),
+                acids,
+                "{}",
+                expr
+            );
+            for _ in 0..4 {
+                let ctx = random_variables(&mut rng).context();
+                // Debug output compares NaN with itself and errors with each other
+                assert_eq!(
+                    format!("{:?}", tree.eval_with_context(&ctx)),
+                    format!("{:?}", decoded.eval_with_context(&ctx)),
+                    "{}",
+                    expr
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn last_index_detects_stop() {
+        // Data (6) + stop + trailing data
         let sequence = [
-            AminoAcid::P, // 0
-            AminoAcid::F, // 1
-            AminoAcid::A, // 2
-            AminoAcid::F, // 3
-            AminoAcid::F, // 4
-            AminoAcid::A, // 5
-            AminoAcid::UNKNOWN, // promoter start at index 6
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN, // promoter end at index 9
+            AminoAcid::P,    // 0
+            AminoAcid::F,    // 1
+            AminoAcid::A,    // 2
+            AminoAcid::F,    // 3
+            AminoAcid::F,    // 4
+            AminoAcid::A,    // 5
+            AminoAcid::STOP, // stop at index 6
             AminoAcid::P,
             AminoAcid::F,
         ];
 
-        let idx = last_idx_before_promoter(&sequence, 4);
+        let idx = last_idx_before_stop(&sequence);
         assert_eq!(idx, 6);
         let expr_segment = &sequence[0..idx];
         assert_eq!(expr_segment.len(), 6);
+        assert_eq!(consumed_through_stop(&sequence, idx), 7);
     }
 
     #[test]
-    fn last_index_without_promoter_returns_len_minus_one() {
+    fn last_index_without_stop_returns_len() {
         let sequence = [
             AminoAcid::P,
             AminoAcid::F,
@@ -139,7 +1041,8 @@ mod tests {
             AminoAcid::F,
             AminoAcid::A,
         ];
-        let idx = last_idx_before_promoter(&sequence, 4);
-        assert_eq!(idx, sequence.len() - 1);
+        let idx = last_idx_before_stop(&sequence);
+        assert_eq!(idx, sequence.len());
+        assert_eq!(consumed_through_stop(&sequence, idx), sequence.len());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,172 @@
+use evalexpr::{
+    ContextWithMutableFunctions, ContextWithMutableVariables, DefaultNumericTypes, EvalexprResult,
+    Function, HashMapContext, Value,
+};
+
+use crate::components::neurotransmitters::{
+    ACCUMULATOR_NAMES, NEUROTRANSMITTER_COUNT, NEUROTRANSMITTER_NAMES, Neurotransmitter,
+    RECEPTOR_NAMES,
+};
+
+// The random variable is drawn uniformly below this, so genes can compare it against a per mille
+// chance
+pub const RANDOM_RANGE: u32 = 1000;
+
+// Every neurotransmitter gives expressions its level, its receptors and its accumulator to read,
+// the rest are about the neuron and the simulation
+pub const VARIABLE_COUNT: usize = 3 * NEUROTRANSMITTER_COUNT + 3;
+
+/**
+ * Everything an expression gene can read about its neuron.  Activations and update functions are
+ * evaluated with the same variables, each field is read under the names listed in NAMES.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExprVariables {
+    // Levels inside the neuron, indexed by Neurotransmitter::INDEX like the other arrays
+    pub levels: [u32; NEUROTRANSMITTER_COUNT],
+    // What the receptors of the neuron took up this tick, summed per neurotransmitter
+    pub received: [u32; NEUROTRANSMITTER_COUNT],
+    // How much the neuron has built up to release, 0 without an accumulator
+    pub built_up: [u32; NEUROTRANSMITTER_COUNT],
+    pub ticks_since_fire: u32,
+    // Ticks since the simulation started
+    pub time: u64,
+    // A seeded draw below RANDOM_RANGE, the same for the whole tick
+    pub random: u32,
+}
+
+impl ExprVariables {
+    pub const NAMES: [&'static str; VARIABLE_COUNT] = {
+        let mut names = [""; VARIABLE_COUNT];
+        let mut i = 0;
+        while i < NEUROTRANSMITTER_COUNT {
+            names[i] = NEUROTRANSMITTER_NAMES[i];
+            names[NEUROTRANSMITTER_COUNT + i] = RECEPTOR_NAMES[i];
+            names[2 * NEUROTRANSMITTER_COUNT + i] = ACCUMULATOR_NAMES[i];
+            i += 1;
+        }
+        names[3 * NEUROTRANSMITTER_COUNT] = "ticks_since_fire";
+        names[3 * NEUROTRANSMITTER_COUNT + 1] = "time";
+        names[3 * NEUROTRANSMITTER_COUNT + 2] = "random";
+        names
+    };
+
+    // The values in the order of NAMES
+    pub fn values(&self) -> [i64; VARIABLE_COUNT] {
+        let mut values = [0; VARIABLE_COUNT];
+        let per_neurotransmitter = self
+            .levels
+            .iter()
+            .chain(&self.received)
+            .chain(&self.built_up);
+        for (value, &variable) in values.iter_mut().zip(per_neurotransmitter) {
+            *value = variable.into();
+        }
+        values[3 * NEUROTRANSMITTER_COUNT..].copy_from_slice(&[
+            self.ticks_since_fire.into(),
+            i64::try_from(self.time).unwrap_or(i64::MAX),
+            self.random.into(),
+        ]);
+        values
+    }
+
+    pub fn with_level<T: Neurotransmitter>(mut self, level: u32) -> Self {
+        self.levels[T::INDEX] = level;
+        self
+    }
+
+    // For evaluating trees with evalexpr itself, CompiledExpr gives the same results without
+    // building a map every time
+    pub fn context(&self) -> HashMapContext<DefaultNumericTypes> {
+        let mut context = HashMapContext::new();
+        for (name, function) in [("sigmoid", sigmoid as NativeFn), ("clamp", clamp)] {
+            context
+                .set_function(name.to_string(), Function::new(function))
+                .expect("Functions can always be set");
+        }
+        for (name, value) in Self::NAMES.into_iter().zip(self.values()) {
+            context
+                .set_value(name.to_string(), Value::from_int(value))
+                .expect("Variables are only set once");
+        }
+        context
+    }
+}
+
+type NativeFn = fn(&Value<DefaultNumericTypes>) -> EvalexprResult<Value<DefaultNumericTypes>>;
+
+// The functions genes call that evalexpr doesn't have built in, the logistic function first
+fn sigmoid(argument: &Value<DefaultNumericTypes>) -> EvalexprResult<Value<DefaultNumericTypes>> {
+    let x = argument.as_number()?;
+    Ok(Value::Float(1.0 / (1.0 + (-x).exp())))
+}
+
+// Bounds the first argument by the other two.  Unlike f64::clamp the lower bound wins when the
+// bounds are the wrong way around, rather than panicking.
+fn clamp(argument: &Value<DefaultNumericTypes>) -> EvalexprResult<Value<DefaultNumericTypes>> {
+    let arguments = argument.as_fixed_len_tuple(3)?;
+    if let [Value::Int(x), Value::Int(low), Value::Int(high)] = arguments[..] {
+        return Ok(Value::Int(x.min(high).max(low)));
+    }
+    let [x, low, high] = [0, 1, 2].map(|i| arguments[i].as_number());
+    Ok(Value::Float(x?.min(high?).max(low?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use evalexpr::{DefaultNumericTypes, Value, build_operator_tree};
+
+    use std::array::from_fn;
+
+    use super::ExprVariables;
+    use crate::components::{Dopamine, neurotransmitters::NEUROTRANSMITTER_COUNT};
+
+    #[test]
+    fn every_variable_is_readable() {
+        // Every variable gets its position in NAMES plus one
+        let count = NEUROTRANSMITTER_COUNT as u32;
+        let variables = ExprVariables {
+            levels: from_fn(|i| i as u32 + 1),
+            received: from_fn(|i| count + i as u32 + 1),
+            built_up: from_fn(|i| 2 * count + i as u32 + 1),
+            ticks_since_fire: 3 * count + 1,
+            time: (3 * count + 2).into(),
+            random: 3 * count + 3,
+        };
+        let context = variables.context();
+
+        for (value, name) in (1..).zip(ExprVariables::NAMES) {
+            let tree = build_operator_tree::<DefaultNumericTypes>(name).unwrap();
+            assert_eq!(tree.eval_int_with_context(&context), Ok(value), "{}", name);
+        }
+    }
+
+    #[test]
+    fn gene_functions_evaluate() {
+        let context = ExprVariables::default().with_level::<Dopamine>(5).context();
+        let eval = |expr: &str| {
+            build_operator_tree::<DefaultNumericTypes>(expr)
+                .unwrap()
+                .eval_with_context(&context)
+        };
+
+        assert_eq!(eval("sigmoid(0)"), Ok(Value::Float(0.5)));
+        assert!(eval("sigmoid(dopamine * 10)").unwrap().as_float().unwrap() > 0.99);
+        assert_eq!(eval("clamp(dopamine, 0, 3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("clamp(-dopamine, 0, 3)"), Ok(Value::Int(0)));
+        assert_eq!(eval("clamp(dopamine, 7, 3)"), Ok(Value::Int(7)));
+        assert_eq!(eval("clamp(0.5, 0, 3)"), Ok(Value::Float(0.5)));
+        assert_eq!(eval("min(dopamine, 2)"), Ok(Value::Int(2)));
+        assert_eq!(eval("max(dopamine, 2)"), Ok(Value::Int(5)));
+        assert_eq!(eval("math::abs(-dopamine)"), Ok(Value::Int(5)));
+        assert_eq!(eval("math::tanh(0)"), Ok(Value::Float(0.0)));
+        assert_eq!(
+            eval("if(dopamine > 2 && !(dopamine > 9), 1, 2)"),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(
+            eval("dopamine < 2 || dopamine == 5"),
+            Ok(Value::Boolean(true))
+        );
+    }
+}
@@ -1,5 +1,9 @@
 use bevy::prelude::*;
-use gene_traits::{dna, rna};
+use gene_traits::{
+    dna,
+    packed::{NucleotideSequence, PackedSequence},
+    rna,
+};
 
 #[derive(Component)]
 pub struct GeneParser {}
@@ -10,13 +14,14 @@ pub struct AttachedGenome {
     pub start_index: usize,
 }
 
-// A component to store the full genome on a separate entity
+// A component to store the full genome on a separate entity.  Bases are packed 4 to a byte, since
+// every organism in a population carries one of these.
 #[derive(Component)]
 pub struct Genome {
-    pub sequence: Vec<dna::Nucleotide>,
+    pub sequence: PackedSequence,
 }
 
-const TATA_BOXES: [[dna::Nucleotide; 4]; 4] = [
+pub(crate) const TATA_BOXES: [[dna::Nucleotide; 4]; 4] = [
     [
         dna::Nucleotide::A,
         dna::Nucleotide::T,
@@ -43,71 +48,142 @@ const TATA_BOXES: [[dna::Nucleotide; 4]; 4] = [
     ],
 ];
 
+// Transcribed genes are spawned as children of the organism that carries the AttachedGenome, so
+// that the translated proteins can find their way back to it.
 #[derive(Component, Deref, PartialEq, Eq, Debug)]
-pub struct RnaStrand(Vec<rna::Nucleotide>);
+pub struct RnaStrand(pub Vec<rna::Nucleotide>);
+
+// Where a gene sits inside a genome.  The promoter that opened the gene starts at
+// promoter_index, and the transcribed bases are start..end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneLocation {
+    pub promoter: [dna::Nucleotide; 4],
+    pub promoter_index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Every gene found in a genome, kept next to the Genome it describes
+#[derive(Component, Debug, Default, PartialEq, Eq)]
+pub struct GenomeAnnotation {
+    pub genes: Vec<GeneLocation>,
+}
+
+impl GenomeAnnotation {
+    pub fn new<S: NucleotideSequence + ?Sized>(genome: &S) -> Self {
+        Self {
+            genes: scan_genes(genome).collect(),
+        }
+    }
+}
+
+/**
+ * Walks a genome one 4 base window at a time.  A gene opens at a TATA box and runs until the next
+ * one, transcribing the first base of every window in between.  This means a gene also picks up
+ * the tail of its own promoter, and the last 3 bases of the genome are never transcribed.
+ * Back to back promoters only open a single gene, using the last promoter of the run.
+ */
+pub struct GeneScanner<'a, S: NucleotideSequence + ?Sized> {
+    genome: &'a S,
+    index: usize,
+}
+
+pub fn scan_genes<S: NucleotideSequence + ?Sized>(genome: &S) -> GeneScanner<'_, S> {
+    GeneScanner { genome, index: 0 }
+}
+
+impl<S: NucleotideSequence + ?Sized> GeneScanner<'_, S> {
+    fn window_count(&self) -> usize {
+        self.genome.len().saturating_sub(3)
+    }
+
+    fn window(&self, index: usize) -> [dna::Nucleotide; 4] {
+        [0, 1, 2, 3].map(|offset| self.genome.nucleotide(index + offset))
+    }
+
+    fn is_promoter(&self, index: usize) -> bool {
+        TATA_BOXES.contains(&self.window(index))
+    }
+}
+
+impl<S: NucleotideSequence + ?Sized> Iterator for GeneScanner<'_, S> {
+    type Item = GeneLocation;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let windows = self.window_count();
+        let mut promoter_index = (self.index..windows).find(|&i| self.is_promoter(i))?;
+        while promoter_index + 1 < windows && self.is_promoter(promoter_index + 1) {
+            promoter_index += 1;
+        }
+
+        let start = promoter_index + 1;
+        let end = (start..windows)
+            .find(|&i| self.is_promoter(i))
+            .unwrap_or(windows);
+        self.index = end;
+
+        // Only possible when the genome ends on a promoter
+        if end <= start {
+            return None;
+        }
+
+        Some(GeneLocation {
+            promoter: self.window(promoter_index),
+            promoter_index,
+            start,
+            end,
+        })
+    }
+}
+
+pub fn transcribe<S: NucleotideSequence + ?Sized>(genome: &S, gene: &GeneLocation) -> RnaStrand {
+    RnaStrand(
+        (gene.start..gene.end)
+            .map(|i| genome.nucleotide(i).into())
+            .collect(),
+    )
+}
+
+// Keeps the annotation in step with the genome, including after it gets modified
+pub fn annotate_genome(query: Query<(Entity, &Genome), Changed<Genome>>, mut commands: Commands) {
+    for (entity, genome) in query.iter() {
+        commands
+            .entity(entity)
+            .insert(GenomeAnnotation::new(&genome.sequence));
+    }
+}
 
 pub fn parse_attached_genome(
-    query: Query<&AttachedGenome, With<GeneParser>>,
+    query: Query<(Entity, &AttachedGenome), With<GeneParser>>,
     genomes: Query<&Genome>,
     mut commands: Commands,
 ) {
-    for attached in query.iter() {
+    for (organism, attached) in query.iter() {
         let Ok(genome) = genomes.get(attached.genome_entity) else {
             continue;
         };
         let start = attached.start_index.min(genome.sequence.len());
-        let slice = &genome.sequence[start..];
-        if let Some(rna_strand) = parse_gene(slice) {
-            commands.spawn(rna_strand);
+        let slice = genome.sequence.slice(start..);
+        for gene in scan_genes(&slice) {
+            commands.spawn((transcribe(&slice, &gene), ChildOf(organism)));
         }
+        // The genome only needs to be transcribed once
+        commands.entity(organism).remove::<GeneParser>();
     }
 }
 
-fn parse_gene(genome: &[dna::Nucleotide]) -> Option<RnaStrand> {
-    let gene_window: &mut [dna::Nucleotide; 4] = &mut [dna::Nucleotide::A; 4];
-
-    let mut rna_strand: Vec<rna::Nucleotide> = Vec::new();
-
-    let mut found_start = false;
-    let mut found_end = false;
-
-    for i in 3..genome.len() {
-        gene_window[0] = genome[i - 3];
-        gene_window[1] = genome[i - 2];
-        gene_window[2] = genome[i - 1];
-        gene_window[3] = genome[i];
-        if TATA_BOXES.contains(gene_window) {
-            if found_start && found_end {
-                if rna_strand.len() > 0 {
-                    return Some(RnaStrand(rna_strand));
-                }
-                return None;
-            }
-            found_start = true;
-            continue;
-        }
-        found_end = true;
-
-        rna_strand.push(gene_window[0].into());
-    }
-
-    if rna_strand.len() > 0 {
-        return Some(RnaStrand(rna_strand));
-    }
-    None
-}
-
 #[cfg(test)]
 mod test {
-    use super::{AttachedGenome, GeneParser, Genome, RnaStrand, parse_attached_genome};
+    use super::{
+        AttachedGenome, GeneLocation, GeneParser, Genome, GenomeAnnotation, RnaStrand,
+        annotate_genome, parse_attached_genome, scan_genes, transcribe,
+    };
     use bevy::{
         app::{App, Update},
         ecs::world::World,
     };
     use gene_traits::{dna, rna};
 
-    use crate::components::gene_reader::parse_gene;
-
     #[test]
     fn get_rna_strand() {
         let genome = [
@@ -131,7 +207,7 @@ mod test {
             rna::Nucleotide::A,
         ]);
 
-        let result = parse_gene(&genome).unwrap();
+        let result = transcribe(&genome, &scan_genes(&genome).next().unwrap());
 
         assert_eq!(result, expected);
     }
@@ -149,7 +225,7 @@ mod test {
 
         let expected = RnaStrand(vec![rna::Nucleotide::U, rna::Nucleotide::U]);
 
-        let result = parse_gene(&genome).unwrap();
+        let result = transcribe(&genome, &scan_genes(&genome).next().unwrap());
 
         assert_eq!(result, expected);
     }
@@ -182,7 +258,12 @@ mod test {
         app.add_systems(Update, parse_attached_genome);
 
         // Spawn the genome holder entity
-        let genome_entity = app.world_mut().spawn(Genome { sequence: genome }).id();
+        let genome_entity = app
+            .world_mut()
+            .spawn(Genome {
+                sequence: genome.into(),
+            })
+            .id();
 
         // Spawn an entity with the parser and an attachment referencing the genome entity
         app.world_mut().spawn((
@@ -245,7 +326,12 @@ mod test {
         let mut app = App::new();
         app.add_systems(Update, parse_attached_genome);
 
-        let genome_entity = app.world_mut().spawn(Genome { sequence: genome }).id();
+        let genome_entity = app
+            .world_mut()
+            .spawn(Genome {
+                sequence: genome.into(),
+            })
+            .id();
 
         app.world_mut().spawn((
             GeneParser {},
@@ -267,4 +353,151 @@ mod test {
             "No spawned RnaStrand matched the expected sequence for the given start index"
         );
     }
+
+    // Three promoters, each opening a gene that runs up to the next one
+    fn three_gene_genome() -> Vec<dna::Nucleotide> {
+        vec![
+            dna::Nucleotide::A,
+            dna::Nucleotide::T,
+            dna::Nucleotide::T,
+            dna::Nucleotide::A, // promoter at 0
+            dna::Nucleotide::G,
+            dna::Nucleotide::A,
+            dna::Nucleotide::T,
+            dna::Nucleotide::A,
+            dna::Nucleotide::A,
+            dna::Nucleotide::T, // promoter at 6
+            dna::Nucleotide::C,
+            dna::Nucleotide::C,
+            dna::Nucleotide::C,
+            dna::Nucleotide::T,
+            dna::Nucleotide::T,
+            dna::Nucleotide::T,
+            dna::Nucleotide::A, // promoter at 13
+            dna::Nucleotide::G,
+            dna::Nucleotide::G,
+            dna::Nucleotide::C,
+            dna::Nucleotide::C,
+            dna::Nucleotide::C,
+        ]
+    }
+
+    #[test]
+    fn scanner_finds_every_gene() {
+        let genome = three_gene_genome();
+
+        let genes: Vec<GeneLocation> = scan_genes(&genome).collect();
+
+        assert_eq!(
+            genes,
+            vec![
+                GeneLocation {
+                    promoter: [
+                        dna::Nucleotide::A,
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::A,
+                    ],
+                    promoter_index: 0,
+                    start: 1,
+                    end: 6,
+                },
+                GeneLocation {
+                    promoter: [
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::A,
+                        dna::Nucleotide::A,
+                        dna::Nucleotide::T,
+                    ],
+                    promoter_index: 6,
+                    start: 7,
+                    end: 13,
+                },
+                GeneLocation {
+                    promoter: [
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::T,
+                        dna::Nucleotide::A,
+                    ],
+                    promoter_index: 13,
+                    start: 14,
+                    end: 19,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn scanner_ignores_bases_before_the_first_promoter_and_a_trailing_promoter() {
+        let genome = [
+            dna::Nucleotide::G,
+            dna::Nucleotide::C,
+            dna::Nucleotide::A,
+            dna::Nucleotide::T,
+            dna::Nucleotide::T,
+            dna::Nucleotide::A,
+        ];
+
+        assert_eq!(scan_genes(&genome).count(), 0);
+        assert_eq!(scan_genes(&[]).count(), 0);
+    }
+
+    #[test]
+    fn integration_annotates_genome() {
+        let mut app = App::new();
+        app.add_systems(Update, annotate_genome);
+
+        let genome_entity = app
+            .world_mut()
+            .spawn(Genome {
+                sequence: three_gene_genome().into(),
+            })
+            .id();
+
+        app.update();
+
+        let annotation = app
+            .world()
+            .get::<GenomeAnnotation>(genome_entity)
+            .expect("Genome should have been annotated");
+        assert_eq!(*annotation, GenomeAnnotation::new(&three_gene_genome()));
+        assert_eq!(annotation.genes.len(), 3);
+    }
+
+    #[test]
+    fn integration_spawns_rna_for_every_gene() {
+        let mut app = App::new();
+        app.add_systems(Update, parse_attached_genome);
+
+        let genome_entity = app
+            .world_mut()
+            .spawn(Genome {
+                sequence: three_gene_genome().into(),
+            })
+            .id();
+        app.world_mut().spawn((
+            GeneParser {},
+            AttachedGenome {
+                genome_entity,
+                start_index: 0,
+            },
+        ));
+
+        app.update();
+
+        let world: &mut World = app.world_mut();
+        let mut query = world.query::<&RnaStrand>();
+        let strands: Vec<&RnaStrand> = query.iter(app.world()).collect();
+
+        assert_eq!(strands.len(), 3);
+        assert!(strands.contains(&&RnaStrand(vec![
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::U,
+            rna::Nucleotide::C,
+            rna::Nucleotide::C,
+            rna::Nucleotide::C,
+        ])));
+    }
 }
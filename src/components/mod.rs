@@ -1,11 +1,15 @@
 pub mod accumulator;
 pub mod activation;
 pub mod amino_acid_reader;
+pub mod clearance;
+pub mod compiled_expr;
 pub mod gene_reader;
 pub mod neuron;
 pub mod neurotransmitters;
 pub mod receptor;
-mod expr_gene;
+pub mod expr_gene;
+pub mod expr_variables;
+pub mod recombination;
 
 pub use super::activation::Activation;
 pub use super::neuron::*;
@@ -1,89 +1,236 @@
 use bevy::ecs::{component::Component, entity::Entity};
-use evalexpr::{build_operator_tree, context_map, DefaultNumericTypes, Node};
+use evalexpr::{build_operator_tree, DefaultNumericTypes, Node};
 use bevy::ecs::system::EntityCommands;
 use gene_traits::amino_acid::AminoAcid;
-use crate::config::PROMOTER_SIZE;
-use crate::components::expr_gene::{expr_from_amino_acids, last_idx_before_promoter};
-use crate::ComponentRegister;
-use gene_traits::{mul, register_gene};
-use gene_traits::dna::get_header;
-use hashed_type_def::HashedTypeDef;
+use crate::component_register::{GeneEncoder, GeneParseError};
+use crate::components::compiled_expr::CompiledExpr;
+use crate::components::compiled_expr::ExprValue;
+use crate::components::expr_gene::{amino_acids_from_assignment_expr, amino_acids_from_assignments, assignments, assignments_from_amino_acids, consumed_through_stop, last_idx_before_stop};
+use crate::components::expr_variables::ExprVariables;
+use crate::components::neurotransmitters::{
+    NEUROTRANSMITTER_COUNT, NEUROTRANSMITTER_NAMES, Neurotransmitter,
+};
+use gene_traits::header::Gene;
 
 #[derive(Component, Default, Debug)]
 pub struct Neuron {
-    pub dopamine: u32,
-    pub serotonin: u32,
-    pub norepinephrine: u32,
-    pub dopamine_receptors: Vec<Entity>,
-    pub serotonin_receptors: Vec<Entity>,
-    pub norepinephrine_receptors: Vec<Entity>,
+    // Indexed by Neurotransmitter::INDEX, like the receptors
+    pub levels: [u32; NEUROTRANSMITTER_COUNT],
+    // Counts up every tick the synapse stays quiet, firing resets it
+    pub ticks_since_fire: u32,
+    pub receptors: [Vec<Entity>; NEUROTRANSMITTER_COUNT],
 }
 
-#[derive(Component, Debug)]
+impl Neuron {
+    pub fn level<T: Neurotransmitter>(&self) -> u32 {
+        self.levels[T::INDEX]
+    }
+
+    pub fn level_mut<T: Neurotransmitter>(&mut self) -> &mut u32 {
+        &mut self.levels[T::INDEX]
+    }
+
+    pub fn receptors<T: Neurotransmitter>(&self) -> &[Entity] {
+        &self.receptors[T::INDEX]
+    }
+
+    pub fn with_level<T: Neurotransmitter>(mut self, level: u32) -> Self {
+        self.levels[T::INDEX] = level;
+        self
+    }
+
+    pub fn with_receptors<T: Neurotransmitter>(mut self, receptors: Vec<Entity>) -> Self {
+        self.receptors[T::INDEX] = receptors;
+        self
+    }
+}
+
+#[derive(Component, Debug, Gene)]
+// Version 3 genes spell a list of assignments, version 2 genes a single typed prefix expression
+#[gene(id = "update_function", version = 3, salt = 0)]
 pub struct UpdateFunction {
-    pub func: evalexpr::EvalexprResult<Node<DefaultNumericTypes>, DefaultNumericTypes>,
+    func: Node<DefaultNumericTypes>,
+    // Indices into TARGETS with what is assigned to them, in the order they are applied
+    assignments: Vec<(usize, CompiledExpr)>,
 }
 
-// Tag type to enable registration via register_gene!
-#[derive(HashedTypeDef)]
-pub struct UpdateFunctionTag {}
+// Reads a level out of what an assignment evaluated to.  Numbers are truncated and saturated to the
+// range of a level, conditions and NaN aren't levels.
+fn level_of(value: ExprValue) -> Option<u32> {
+    match value {
+        ExprValue::Int(int) => Some(int.clamp(0, u32::MAX.into()) as u32),
+        ExprValue::Float(float) if !float.is_nan() => Some(float as u32),
+        _ => None,
+    }
+}
 
 impl UpdateFunction {
-    pub fn eval(&self, neuron: &mut Neuron) {
-        let context : evalexpr::HashMapContext<DefaultNumericTypes> = context_map! {
-            "dopamine" => int neuron.dopamine,
-            "serotonin" => int neuron.serotonin,
-            "norepinephrine" => int neuron.norepinephrine
-        }
-        .unwrap();
+    // The neuron state update genes can assign, in the order of Neuron::levels
+    pub const TARGETS: [&'static str; NEUROTRANSMITTER_COUNT] = NEUROTRANSMITTER_NAMES;
+
+    pub fn new(func: Node<DefaultNumericTypes>) -> Result<Self, String> {
+        let assignments = assignments(&func)?
+            .into_iter()
+            .map(|(target, value)| {
+                let target = Self::TARGETS
+                    .iter()
+                    .position(|name| *name == target)
+                    .ok_or_else(|| format!("{} can't be assigned", target))?;
+                Ok((target, CompiledExpr::compile(value)?))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { func, assignments })
+    }
 
-        // What exactly should I do here?
-        // First, update the internal neurotransmitter levels based on the receptors... that I didn't take in here...
-        let _ = &context;
+    pub fn expr(&self) -> &Node<DefaultNumericTypes> {
+        &self.func
     }
 
-    // Wrapper provided to mirror Activation's API and reuse common logic if needed elsewhere
-    fn parse_amino_acids(sequence: &[AminoAcid]) -> String {
-        expr_from_amino_acids(sequence)
+    /**
+     * Applies the assignments in order, each one seeing what was assigned before it.  An assignment
+     * that fails to evaluate, e.g. on a division by zero, or doesn't give a number leaves its
+     * target as it was.  The other assignments still apply.
+     */
+    pub fn eval(&self, neuron: &mut Neuron, variables: &ExprVariables) {
+        let mut variables = *variables;
+        for (target, compiled) in &self.assignments {
+            let Some(level) = compiled.eval(&variables).ok().and_then(level_of) else {
+                continue;
+            };
+            neuron.levels[*target] = level;
+            variables.levels[*target] = level;
+        }
     }
 
     // Gene parser for UpdateFunction using the shared helpers
-    pub fn sequence_parser(sequence: &[AminoAcid], mut commands: EntityCommands) -> usize {
-        let last_idx = last_idx_before_promoter(sequence, PROMOTER_SIZE);
+    pub fn sequence_parser(
+        sequence: &[AminoAcid],
+        mut commands: EntityCommands,
+    ) -> Result<usize, GeneParseError> {
+        let last_idx = last_idx_before_stop(sequence);
+        let consumed = consumed_through_stop(sequence, last_idx);
+
+        let formula = assignments_from_amino_acids(&sequence[0..last_idx], &Self::TARGETS);
+
+        let update_fn = build_operator_tree::<DefaultNumericTypes>(&formula)
+            .map_err(|e| e.to_string())
+            .and_then(UpdateFunction::new)
+            .map_err(|e| GeneParseError {
+                consumed,
+                message: format!("update function {:?} doesn't compile: {}", formula, e),
+            })?;
 
-        let formula = expr_from_amino_acids(&sequence[0..last_idx]);
+        commands.insert(update_fn);
 
-        let precompiled = build_operator_tree::<DefaultNumericTypes>(&formula)
-            .expect("Failed to precompile update function");
+        Ok(consumed)
+    }
+}
 
-        let update_fn = UpdateFunction { func: Ok(precompiled) };
+impl GeneEncoder for UpdateFunction {
+    fn encode_payload(&self) -> Result<Vec<AminoAcid>, String> {
+        amino_acids_from_assignments(&self.func, &Self::TARGETS)
+    }
 
-        commands.insert(update_fn);
+    fn payload_from_text(text: &str) -> Result<Vec<AminoAcid>, String> {
+        amino_acids_from_assignment_expr(text, &Self::TARGETS)
+    }
 
-        last_idx
+    fn payload_to_text(payload: &[AminoAcid]) -> String {
+        assignments_from_amino_acids(payload, &Self::TARGETS)
     }
 }
 
 impl Default for UpdateFunction {
     fn default() -> Self {
-        Self { func: build_operator_tree::<DefaultNumericTypes>("") }
+        // Assigns nothing, which leaves the neuron as it is
+        let func = build_operator_tree::<DefaultNumericTypes>("").expect("Empty expressions parse");
+        Self::new(func).expect("Nothing to compile")
     }
 }
 
-pub fn update_function_parser(sequence: &[AminoAcid], commands: EntityCommands) -> usize {
-    UpdateFunction::sequence_parser(sequence, commands)
-}
-
-register_gene!(
-    UpdateFunction,
-    { UpdateFunctionTag::TYPE_HASH_NATIVE },
-    update_function_parser,
-    { PROMOTER_SIZE }
-);
-
 // A synapse is in itself a separate entity, and each neuron can contain the same synapses for
 // different purposes.  This allows the synapse state to be propagated.
 #[derive(Component, Default, Debug)]
 pub struct Synapse {
     pub active: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use evalexpr::{build_operator_tree, DefaultNumericTypes};
+
+    use super::{Neuron, UpdateFunction};
+    use crate::components::expr_variables::ExprVariables;
+    use crate::components::neurotransmitters::NEUROTRANSMITTER_COUNT;
+
+    fn update(expr: &str) -> UpdateFunction {
+        UpdateFunction::new(build_operator_tree::<DefaultNumericTypes>(expr).unwrap()).unwrap()
+    }
+
+    // The levels of the first neurotransmitters, the rest are 0
+    fn levels(first: &[u32]) -> [u32; NEUROTRANSMITTER_COUNT] {
+        let mut levels = [0; NEUROTRANSMITTER_COUNT];
+        levels[..first.len()].copy_from_slice(first);
+        levels
+    }
+
+    // Runs the update on a neuron with the given levels, the way update_neuron does
+    fn run(expr: &str, levels: [u32; NEUROTRANSMITTER_COUNT]) -> [u32; NEUROTRANSMITTER_COUNT] {
+        let mut neuron = Neuron {
+            levels,
+            ..Default::default()
+        };
+        let variables = ExprVariables {
+            levels,
+            ..Default::default()
+        };
+        update(expr).eval(&mut neuron, &variables);
+        neuron.levels
+    }
+
+    #[test]
+    fn assignments_update_the_neuron() {
+        assert_eq!(
+            run("dopamine = dopamine * 0.9; serotonin = serotonin + 1", levels(&[100, 5, 7])),
+            levels(&[90, 6, 7])
+        );
+        // Later assignments see the earlier ones
+        assert_eq!(
+            run("dopamine = 3; serotonin = dopamine * 2", levels(&[])),
+            levels(&[3, 6])
+        );
+        assert_eq!(
+            run("dopamine = 1; dopamine = dopamine + 1", levels(&[])),
+            levels(&[2])
+        );
+        // Levels can't go below 0 or past u32::MAX
+        assert_eq!(
+            run("dopamine = dopamine - 10; serotonin = 2 ^ 40", levels(&[5])),
+            levels(&[0, u32::MAX])
+        );
+        assert_eq!(run("", levels(&[1, 2, 3])), levels(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn failed_assignments_leave_their_target() {
+        assert_eq!(
+            run(
+                "dopamine = dopamine / 0; serotonin = 4; norepinephrine = 1 < 2",
+                levels(&[1, 2, 3])
+            ),
+            levels(&[1, 4, 3])
+        );
+        assert_eq!(
+            run("dopamine = math::ln(-1)", levels(&[1, 2, 3])),
+            levels(&[1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn only_levels_can_be_assigned() {
+        for expr in ["time = 1", "dopamine + 1", "dopamine = unknown"] {
+            let tree = build_operator_tree::<DefaultNumericTypes>(expr).unwrap();
+            assert!(UpdateFunction::new(tree).is_err(), "{}", expr);
+        }
+    }
+}
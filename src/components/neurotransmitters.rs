@@ -1,33 +1,195 @@
-use hashed_type_def::HashedTypeDef;
-use crate::components::Neuron;
+use std::fmt::Debug;
 
-pub trait NeuronUpdater {
-    fn update(level: u32, neuron: &mut Neuron);
+use bevy::ecs::{entity::Entity, system::Query};
+use gene_traits::{amino_acid::AminoAcid, header::Gene};
+
+use crate::components::{Neuron, Receptor, accumulator::Accumulator, clearance::Clearance};
+
+/**
+ * A chemical neurons hold, release and take up.  Every per-neurotransmitter array, the levels in
+ * Neuron and the variables expressions read, is indexed by INDEX, which is the position of the
+ * neurotransmitter in the neurotransmitters! list below.
+ */
+pub trait Neurotransmitter: Send + Sync + Debug + Default + 'static {
+    // What expressions call the level, the receptors and the accumulator add a suffix to it
+    const NAME: &'static str;
+    const INDEX: usize;
+    // The expression gene codes of the level, the receptors and the accumulator
+    const CODES: [[AminoAcid; 2]; 3];
 }
 
-#[derive(Default, Debug, HashedTypeDef)]
-pub struct Dopamine;
+// Something done once for every neurotransmitter, see for_each_neurotransmitter
+pub trait NeurotransmitterVisitor {
+    fn visit<T: Neurotransmitter>(&mut self);
+}
 
-impl NeuronUpdater for Dopamine {
-    fn update(level: u32, neuron: &mut Neuron) {
-        neuron.dopamine += level;
-    }
+/**
+ * Declares the neurotransmitters and registers everything that is kept per neurotransmitter:
+ * its accumulator and clearance genes, the variables expression genes read, the queries
+ * ExprInputs reads them with and the plugins NeurotransmitterPlugins adds.  Each line names the
+ * type, what expressions call it, the codes of CODES and the salts that pin the headers of the
+ * accumulator and clearance genes.  The order of the lines is the order of every
+ * per-neurotransmitter array.
+ *
+ * Adding a chemical takes more than its line.  Its codes give meaning to pairs expression genes
+ * used to skip, so every expression gene needs a new version and a new pin in SYMBOLS_PINS.  Its
+ * genes are registered without salts at first, registered_headers_are_pinned then says which
+ * salts to pin them with.
+ */
+macro_rules! neurotransmitters {
+    ($(
+        $ty:ident => $name:ident,
+        [$($level:ident)*], [$($receptor:ident)*], [$($accumulator:ident)*]
+        $(, salts($accumulator_salt:tt, $clearance_salt:tt))?;
+    )*) => {
+        $(
+            // The derive registers the accumulator and clearance genes of the neurotransmitter
+            #[derive(Default, Debug, Gene)]
+            #[gene(
+                Accumulator<$ty>,
+                id = concat!("accumulator.", stringify!($name)),
+                version = 1
+                $(, salt = $accumulator_salt)?
+            )]
+            #[gene(
+                Clearance<$ty>,
+                id = concat!("clearance.", stringify!($name)),
+                version = 1
+                $(, salt = $clearance_salt)?
+            )]
+            pub struct $ty;
+
+            impl Neurotransmitter for $ty {
+                const NAME: &'static str = stringify!($name);
+                const INDEX: usize = index_of(stringify!($name));
+                const CODES: [[AminoAcid; 2]; 3] = [
+                    [$(AminoAcid::$level),*],
+                    [$(AminoAcid::$receptor),*],
+                    [$(AminoAcid::$accumulator),*],
+                ];
+            }
+        )*
+
+        pub const NEUROTRANSMITTER_COUNT: usize = [$(stringify!($name)),*].len();
+        pub const NEUROTRANSMITTER_NAMES: [&str; NEUROTRANSMITTER_COUNT] =
+            [$(stringify!($name)),*];
+        pub const RECEPTOR_NAMES: [&str; NEUROTRANSMITTER_COUNT] =
+            [$(concat!(stringify!($name), "_receptor")),*];
+        pub const ACCUMULATOR_NAMES: [&str; NEUROTRANSMITTER_COUNT] =
+            [$(concat!(stringify!($name), "_accumulator")),*];
+        pub const NEUROTRANSMITTER_CODES: [[[AminoAcid; 2]; 3]; NEUROTRANSMITTER_COUNT] =
+            [$(<$ty as Neurotransmitter>::CODES),*];
+
+        // Visits the neurotransmitters in the order of the list
+        pub fn for_each_neurotransmitter(visitor: &mut impl NeurotransmitterVisitor) {
+            $(visitor.visit::<$ty>();)*
+        }
+
+        // The receptors and accumulators of every neurotransmitter, in the order of the list
+        pub type ReceptorQueries<'w, 's> = ($(Query<'w, 's, &'static Receptor<$ty>>,)*);
+        pub type AccumulatorQueries<'w, 's> = ($(Query<'w, 's, &'static Accumulator<$ty>>,)*);
+
+        // What the receptors of the neuron took up, summed per neurotransmitter and stopping at
+        // u32::MAX.  Receptors that have gone away don't add anything.
+        pub fn received(
+            receptors: &ReceptorQueries,
+            neuron: &Neuron,
+        ) -> [u32; NEUROTRANSMITTER_COUNT] {
+            let ($($name,)*) = receptors;
+            [$(
+                $name
+                    .iter_many(neuron.receptors::<$ty>())
+                    .map(|receptor| receptor.level)
+                    .fold(0, u32::saturating_add)
+            ),*]
+        }
+
+        // How much the neuron has built up to release, 0 without an accumulator
+        pub fn built_up(
+            accumulators: &AccumulatorQueries,
+            entity: Entity,
+        ) -> [u32; NEUROTRANSMITTER_COUNT] {
+            let ($($name,)*) = accumulators;
+            [$($name.get(entity).map_or(0, |accumulator| accumulator.level)),*]
+        }
+    };
 }
 
-#[derive(Default, Debug, HashedTypeDef)]
-pub struct Serotonin;
+const fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
 
-impl NeuronUpdater for Serotonin {
-    fn update(level: u32, neuron: &mut Neuron) {
-        neuron.serotonin += level;
+// Where a name sits in the list, naming a neurotransmitter twice fails to compile
+const fn index_of(name: &str) -> usize {
+    let mut found = None;
+    let mut i = 0;
+    while i < NEUROTRANSMITTER_NAMES.len() {
+        if same_name(NEUROTRANSMITTER_NAMES[i], name) {
+            assert!(found.is_none(), "Neurotransmitters need distinct names");
+            found = Some(i);
+        }
+        i += 1;
     }
+    match found {
+        Some(index) => index,
+        None => panic!("Not a listed neurotransmitter"),
+    }
+}
+
+neurotransmitters! {
+    Dopamine => dopamine, [F A], [F M], [F W], salts(0, 0);
+    Serotonin => serotonin, [F P], [F K], [F T], salts(0, 0);
+    Norepinephrine => norepinephrine, [F F], [F S], [F Y], salts(0, 0);
 }
 
-#[derive(Default, Debug, HashedTypeDef)]
-pub struct Norepinephrine;
+#[cfg(test)]
+mod tests {
+    use super::{
+        ACCUMULATOR_NAMES, Dopamine, NEUROTRANSMITTER_NAMES, Neurotransmitter, Norepinephrine,
+        RECEPTOR_NAMES, Serotonin,
+    };
+    use crate::component_register::HeaderTable;
+    use crate::components::expr_gene::EXPR_SYMBOLS;
+
+    #[test]
+    fn every_neurotransmitter_is_registered() {
+        assert_eq!(
+            [Dopamine::INDEX, Serotonin::INDEX, Norepinephrine::INDEX],
+            [0, 1, 2]
+        );
+        assert_eq!(NEUROTRANSMITTER_NAMES[Serotonin::INDEX], Serotonin::NAME);
 
-impl NeuronUpdater for Norepinephrine {
-    fn update(level: u32, neuron: &mut Neuron) {
-        neuron.dopamine += level;
+        for (index, name) in NEUROTRANSMITTER_NAMES.iter().enumerate() {
+            for gene in ["accumulator", "clearance"] {
+                let id = format!("{}.{}", gene, name);
+                assert!(
+                    HeaderTable::registered()
+                        .iter()
+                        .any(|(register, _)| register.id.name == id),
+                    "{}",
+                    id
+                );
+            }
+            for variable in [name, &RECEPTOR_NAMES[index], &ACCUMULATOR_NAMES[index]] {
+                assert!(
+                    EXPR_SYMBOLS
+                        .iter()
+                        .any(|(_, symbol)| symbol.text == *variable),
+                    "{}",
+                    variable
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,183 @@
+use std::hash::Hash;
+
+use gene_traits::{mutation::seeded_rng, packed::PackedSequence};
+use rand::Rng;
+
+use super::gene_reader::{Genome, scan_genes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossover {
+    // The child starts as the first parent and switches to the second at a random point
+    OnePoint,
+    // The child is the first parent with a random stretch taken from the second
+    TwoPoint,
+    // Every base is taken from either parent at random
+    Uniform,
+    // Like OnePoint, but the switch happens right before a promoter so no gene is cut in half.
+    // Genes are matched up by their position in each genome: the child keeps the genes of the
+    // first parent up to the nth and takes the nth gene and everything after it from the second.
+    GeneAligned,
+}
+
+/**
+ * Produces a child genome from two parents.  Crossover points are limited to the length of the
+ * shorter parent, bases past that come from the first parent for TwoPoint and Uniform, and from
+ * the second parent for OnePoint and GeneAligned.
+ * The same parents, method and seed always give the same child.
+ */
+pub fn crossover<S: Hash>(first: &Genome, second: &Genome, method: Crossover, seed: S) -> Genome {
+    let mut rng = seeded_rng(seed);
+    let first = &first.sequence;
+    let second = &second.sequence;
+    let shortest = first.len().min(second.len());
+
+    let sequence: PackedSequence = match method {
+        Crossover::OnePoint => {
+            let point = rng.random_range(0..=shortest);
+            first
+                .slice(..point)
+                .iter()
+                .chain(second.slice(point..).iter())
+                .collect()
+        }
+        Crossover::TwoPoint => {
+            let a = rng.random_range(0..=shortest);
+            let b = rng.random_range(0..=shortest);
+            let (start, end) = (a.min(b), a.max(b));
+            first
+                .slice(..start)
+                .iter()
+                .chain(second.slice(start..end).iter())
+                .chain(first.slice(end..).iter())
+                .collect()
+        }
+        Crossover::Uniform => first
+            .iter()
+            .zip(second.iter())
+            .map(|(a, b)| if rng.random_bool(0.5) { a } else { b })
+            .chain(first.slice(shortest..).iter())
+            .collect(),
+        Crossover::GeneAligned => {
+            let first_cuts = gene_boundaries(first);
+            let second_cuts = gene_boundaries(second);
+            let gene = rng.random_range(0..first_cuts.len().min(second_cuts.len()));
+            first
+                .slice(..first_cuts[gene])
+                .iter()
+                .chain(second.slice(second_cuts[gene]..).iter())
+                .collect()
+        }
+    };
+
+    Genome { sequence }
+}
+
+// The start of every promoter, followed by the end of the genome
+fn gene_boundaries(genome: &PackedSequence) -> Vec<usize> {
+    scan_genes(genome)
+        .map(|gene| gene.promoter_index)
+        .chain([genome.len()])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use gene_traits::dna;
+
+    use super::{Crossover, crossover};
+    use crate::components::gene_reader::{Genome, scan_genes, transcribe};
+
+    fn genome(base: dna::Nucleotide, length: usize) -> Genome {
+        Genome {
+            sequence: vec![base; length].into(),
+        }
+    }
+
+    // Promoter followed by a body made only of the given base
+    fn genes(body: dna::Nucleotide, count: usize) -> Genome {
+        let mut sequence = Vec::new();
+        for _ in 0..count {
+            sequence.extend_from_slice(&[
+                dna::Nucleotide::T,
+                dna::Nucleotide::A,
+                dna::Nucleotide::T,
+                dna::Nucleotide::A,
+            ]);
+            sequence.extend_from_slice(&[body; 6]);
+        }
+        Genome {
+            sequence: sequence.into(),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_child() {
+        let first = genome(dna::Nucleotide::A, 40);
+        let second = genome(dna::Nucleotide::C, 40);
+
+        for method in [
+            Crossover::OnePoint,
+            Crossover::TwoPoint,
+            Crossover::Uniform,
+            Crossover::GeneAligned,
+        ] {
+            assert_eq!(
+                crossover(&first, &second, method, 3).sequence,
+                crossover(&first, &second, method, 3).sequence
+            );
+        }
+    }
+
+    #[test]
+    fn one_point_switches_parent_once() {
+        let first = genome(dna::Nucleotide::A, 40);
+        let second = genome(dna::Nucleotide::C, 50);
+
+        for seed in 0..20 {
+            let child = crossover(&first, &second, Crossover::OnePoint, seed)
+                .sequence
+                .to_vec();
+            assert_eq!(child.len(), 50);
+            let switch = child
+                .iter()
+                .position(|&n| n == dna::Nucleotide::C)
+                .unwrap_or(child.len());
+            assert!(child[..switch].iter().all(|&n| n == dna::Nucleotide::A));
+            assert!(child[switch..].iter().all(|&n| n == dna::Nucleotide::C));
+        }
+    }
+
+    #[test]
+    fn two_point_and_uniform_keep_first_parent_length() {
+        let first = genome(dna::Nucleotide::A, 40);
+        let second = genome(dna::Nucleotide::C, 30);
+
+        for seed in 0..20 {
+            for method in [Crossover::TwoPoint, Crossover::Uniform] {
+                let child = crossover(&first, &second, method, seed).sequence.to_vec();
+                assert_eq!(child.len(), 40);
+                assert!(child[30..].iter().all(|&n| n == dna::Nucleotide::A));
+            }
+        }
+    }
+
+    #[test]
+    fn gene_aligned_keeps_genes_whole() {
+        let first = genes(dna::Nucleotide::G, 4);
+        let second = genes(dna::Nucleotide::C, 3);
+
+        for seed in 0..20 {
+            let child = crossover(&first, &second, Crossover::GeneAligned, seed);
+            let child_genes: Vec<_> = scan_genes(&child.sequence)
+                .map(|gene| transcribe(&child.sequence, &gene))
+                .collect();
+
+            assert_eq!(child_genes.len(), 3);
+            for gene in child_genes {
+                // Skip the tail of the promoter, the rest must come from a single parent
+                let body = &gene[3..];
+                assert!(body.iter().all(|&n| n == body[0]));
+            }
+        }
+    }
+}
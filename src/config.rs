@@ -1 +1,8 @@
+// Length of a gene header in amino acids.  Headers are derived, matched and skipped with this
+// length, so raising it makes accidental header matches rarer.
 pub const PROMOTER_SIZE: usize = 4;
+
+// Every pair of registered headers is kept at least this many edits apart.  Anything above the
+// ambiguity distance means no window of a chain can be read as two different genes.
+pub const HEADER_MIN_DISTANCE: usize =
+    crate::component_register::ambiguity_distance(PROMOTER_SIZE) + 1;
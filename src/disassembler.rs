@@ -0,0 +1,254 @@
+use std::fmt::{self, Display};
+
+use bevy::ecs::world::World;
+use gene_traits::amino_acid::{AminoAcid, GeneticCode};
+
+use crate::{
+    component_register::{ComponentRegister, HeaderTable},
+    components::{
+        amino_acid_reader::GeneOutcome,
+        expr_gene::last_idx_before_stop,
+        gene_reader::{GeneLocation, Genome, scan_genes, transcribe},
+    },
+    systems::{ribosome::read_chain, translation::translate},
+};
+
+// A gene of the chain as the ribosome reads it, with its payload written out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedComponent {
+    pub gene: GeneOutcome,
+    // The payload written the way the assembler reads it
+    pub payload: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedGene {
+    pub location: GeneLocation,
+    pub chain: Vec<AminoAcid>,
+    pub components: Vec<DecodedComponent>,
+}
+
+// The payload the way the assembler reads it, consumed is what the parser took up after the header
+pub fn describe_payload<const N: usize>(
+    register: &ComponentRegister<N>,
+    consumed: &[AminoAcid],
+) -> String {
+    (register.to_text)(&consumed[..last_idx_before_stop(consumed)])
+}
+
+// Decodes the chain with the registered headers
+pub fn decode_chain(chain: &[AminoAcid]) -> Vec<DecodedComponent> {
+    decode_chain_among(chain, HeaderTable::registered())
+}
+
+/**
+ * Reads a chain with the ribosome, but describes each gene instead of keeping the component.  The
+ * parsers build onto a scratch entity that is thrown away.
+ */
+pub fn decode_chain_among<const N: usize>(
+    chain: &[AminoAcid],
+    headers: &HeaderTable<N>,
+) -> Vec<DecodedComponent> {
+    let mut scratch = World::new();
+    let report = read_chain(chain, headers, scratch.commands().spawn_empty());
+    report
+        .genes
+        .into_iter()
+        .map(|gene| {
+            let (register, _) = headers
+                .iter()
+                .find(|(register, _)| register.id == gene.id)
+                .expect("Genes are read with the headers of the table");
+            DecodedComponent {
+                payload: describe_payload(register, &chain[gene.consumed.clone()]),
+                gene,
+            }
+        })
+        .collect()
+}
+
+pub fn disassemble(genome: &Genome, code: &GeneticCode) -> Vec<DecodedGene> {
+    scan_genes(&genome.sequence)
+        .map(|location| {
+            let chain = translate(&transcribe(&genome.sequence, &location), code);
+            DecodedGene {
+                components: decode_chain(&chain),
+                location,
+                chain,
+            }
+        })
+        .collect()
+}
+
+impl Display for DecodedGene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let promoter: String = self
+            .location
+            .promoter
+            .iter()
+            .map(|&n| char::from(n))
+            .collect();
+        let chain: String = self.chain.iter().map(|&acid| char::from(acid)).collect();
+        writeln!(
+            f,
+            "{} promoter at {}, bases {}..{}",
+            promoter, self.location.promoter_index, self.location.start, self.location.end
+        )?;
+        writeln!(f, "  chain: {}", chain)?;
+        if self.components.is_empty() {
+            writeln!(f, "  no registered header")?;
+        }
+        for DecodedComponent { gene, payload } in &self.components {
+            writeln!(
+                f,
+                "  {} (distance {}) header {:?}, consumed {:?}: {}",
+                gene.type_str, gene.distance, gene.header, gene.consumed, payload
+            )?;
+            if let Some(error) = &gene.error {
+                writeln!(f, "    failed: {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{system::EntityCommands, world::EntityRef};
+    use gene_traits::{
+        amino_acid::{AminoAcid, GeneticCode},
+        dna::Nucleotide,
+        header::{Gene, GeneId},
+    };
+
+    use super::{DecodedGene, decode_chain, decode_chain_among, disassemble};
+    use crate::{
+        assembler::{AssemblerOptions, assemble},
+        component_register::{ComponentRegister, GeneParseError, HeaderTable},
+        components::{Activation, Dopamine, accumulator::Accumulator, gene_reader::GeneLocation},
+        config::{HEADER_MIN_DISTANCE, PROMOTER_SIZE},
+    };
+
+    #[test]
+    fn disassembles_what_was_assembled() {
+        let spec = "Activation: dopamine >= 100
+            UpdateFunction: dopamine = dopamine * 9 / 10; serotonin = serotonin + 1
+            Accumulator<Serotonin>: rate 42
+            Clearance<Dopamine>: resting 5 decay 100 reuptake_max 20 reuptake_half 50 degradation 2";
+        let genome = assemble(spec, &AssemblerOptions::default()).unwrap();
+
+        let genes = disassemble(&genome, &GeneticCode::STANDARD);
+        let decoded: Vec<String> = genes
+            .iter()
+            .flat_map(|gene| &gene.components)
+            .map(|c| format!("{}: {}", c.gene.type_str, c.payload))
+            .collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                "Activation: dopamine>=100",
+                "UpdateFunction: dopamine=(dopamine*9)/10; serotonin=serotonin+1",
+                "Accumulator<Serotonin>: rate 42",
+                "Clearance<Dopamine>: resting 5 decay 100 reuptake_max 20 reuptake_half 50 degradation 2"
+            ]
+        );
+        for gene in &genes {
+            let component = &gene.components[0].gene;
+            assert_eq!(component.distance, 0);
+            assert_eq!(component.header, 0..PROMOTER_SIZE);
+            assert_eq!(component.consumed.end, gene.chain.len());
+            assert!(component.error.is_none());
+        }
+
+        // The decoded spec assembles into the same genome again
+        let again = assemble(&decoded.join("\n"), &AssemblerOptions::default()).unwrap();
+        assert_eq!(again.sequence, genome.sequence);
+    }
+
+    #[test]
+    fn decode_reports_header_distance() {
+        let mut chain = crate::amino_acid_header!(Activation).to_vec();
+        chain[1] = if chain[1] == AminoAcid::W {
+            AminoAcid::Y
+        } else {
+            AminoAcid::W
+        };
+        chain.extend_from_slice(&[AminoAcid::F, AminoAcid::A, AminoAcid::STOP, AminoAcid::R]);
+
+        let components = decode_chain(&chain);
+
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].gene.type_str, "Activation");
+        assert_eq!(components[0].gene.distance, 1);
+        assert_eq!(
+            components[0].gene.consumed,
+            PROMOTER_SIZE..PROMOTER_SIZE + 3
+        );
+        assert_eq!(components[0].payload, "dopamine>0");
+    }
+
+    // Takes two amino acids and never builds anything
+    fn broken_gene(_: &[AminoAcid], _: EntityCommands) -> Result<usize, GeneParseError> {
+        Err(GeneParseError {
+            consumed: 2,
+            message: "broken".to_string(),
+        })
+    }
+
+    fn encode_nothing(_: &EntityRef) -> Option<Result<Vec<AminoAcid>, String>> {
+        None
+    }
+
+    fn no_text(_: &str) -> Result<Vec<AminoAcid>, String> {
+        Err("no text".to_string())
+    }
+
+    fn letters(payload: &[AminoAcid]) -> String {
+        payload.iter().map(|&acid| char::from(acid)).collect()
+    }
+
+    static BROKEN: ComponentRegister<PROMOTER_SIZE> = ComponentRegister {
+        id: GeneId::new("broken", 1),
+        salt: None,
+        type_str: "Broken",
+        parser: broken_gene,
+        encoder: encode_nothing,
+        from_text: no_text,
+        to_text: letters,
+    };
+
+    #[test]
+    fn failed_genes_are_shown() {
+        let accumulator = HeaderTable::registered()
+            .iter()
+            .find(|(register, _)| register.id == <Accumulator<Dopamine> as Gene>::ID)
+            .unwrap()
+            .0;
+        let table = HeaderTable::assign([&BROKEN, accumulator], HEADER_MIN_DISTANCE).unwrap();
+        let mut chain = table.header_of(BROKEN.id).unwrap().to_vec();
+        chain.extend_from_slice(&[AminoAcid::C, AminoAcid::E]);
+        chain.extend_from_slice(&table.header_of(accumulator.id).unwrap());
+        chain.extend_from_slice(&[AminoAcid::R, AminoAcid::N, AminoAcid::STOP]);
+        let gene = DecodedGene {
+            components: decode_chain_among(&chain, &table),
+            location: GeneLocation {
+                promoter: [Nucleotide::T, Nucleotide::A, Nucleotide::T, Nucleotide::A],
+                promoter_index: 0,
+                start: 0,
+                end: chain.len() * 3,
+            },
+            chain,
+        };
+
+        assert_eq!(gene.components.len(), 2);
+        assert_eq!(gene.components[0].gene.error.as_deref(), Some("broken"));
+        assert_eq!(gene.components[0].payload, "CE");
+        assert!(gene.components[1].gene.error.is_none());
+        assert_eq!(gene.components[1].payload, "rate 3");
+
+        let printed = gene.to_string();
+        assert!(printed.contains(": CE\n    failed: broken\n"));
+        assert_eq!(printed.matches("failed").count(), 1);
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use gene_traits::{amino_acid::AminoAcid, dna, rna};
+
+use crate::components::{
+    amino_acid_reader::AminoAcidChain,
+    gene_reader::{Genome, RnaStrand},
+};
+
+// Sequence lines are wrapped at this many characters when writing
+pub const LINE_WIDTH: usize = 60;
+
+#[derive(Debug)]
+pub enum FastaError {
+    Io(io::Error),
+    // Lines and columns are counted from 1
+    InvalidCharacter {
+        line: usize,
+        column: usize,
+        character: char,
+    },
+    // Sequence data showed up before any '>' header line
+    MissingHeader {
+        line: usize,
+    },
+    EmptyId {
+        line: usize,
+    },
+}
+
+impl Display for FastaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastaError::Io(e) => write!(f, "{}", e),
+            FastaError::InvalidCharacter {
+                line,
+                column,
+                character,
+            } => write!(
+                f,
+                "invalid character {:?} at line {}, column {}",
+                character, line, column
+            ),
+            FastaError::MissingHeader { line } => {
+                write!(f, "sequence data without a header at line {}", line)
+            }
+            FastaError::EmptyId { line } => write!(f, "header without an id at line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for FastaError {}
+
+impl From<io::Error> for FastaError {
+    fn from(value: io::Error) -> Self {
+        FastaError::Io(value)
+    }
+}
+
+/**
+ * The '>' line of a record.  The first word is the id, the words shaped like key=value that follow
+ * it are metadata and everything from the first other word on is kept as the description.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FastaHeader {
+    pub id: String,
+    pub metadata: Vec<(String, String)>,
+    pub description: String,
+}
+
+impl FastaHeader {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace().peekable();
+        let id = words.next()?.to_string();
+        let mut metadata = Vec::new();
+        while let Some((key, value)) = words.peek().and_then(|word| metadata_pair(word)) {
+            metadata.push((key.to_string(), value.to_string()));
+            words.next();
+        }
+        Some(Self {
+            id,
+            metadata,
+            description: words.collect::<Vec<_>>().join(" "),
+        })
+    }
+
+    // A description starting with a key=value word would be read back as metadata
+    fn check_description(&self) -> io::Result<()> {
+        match self
+            .description
+            .split_whitespace()
+            .next()
+            .and_then(metadata_pair)
+        {
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the description of {} starts with a key=value word, which reads as metadata",
+                    self.id
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn metadata_pair(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=').filter(|(key, _)| !key.is_empty())
+}
+
+impl Display for FastaHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        for (key, value) in &self.metadata {
+            write!(f, " {}={}", key, value)?;
+        }
+        if !self.description.is_empty() {
+            write!(f, " {}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FastaRecord<T> {
+    pub header: FastaHeader,
+    pub sequence: T,
+}
+
+// Anything that can be stored as the sequence of a FASTA record
+pub trait FastaSequence: Sized {
+    type Residue: Copy + Into<char> + TryFrom<char, Error = char>;
+
+    fn residues(&self) -> Vec<Self::Residue>;
+    fn from_residues(residues: Vec<Self::Residue>) -> Self;
+}
+
+impl FastaSequence for Genome {
+    type Residue = dna::Nucleotide;
+
+    fn residues(&self) -> Vec<Self::Residue> {
+        self.sequence.to_vec()
+    }
+
+    fn from_residues(residues: Vec<Self::Residue>) -> Self {
+        Genome {
+            sequence: residues.into(),
+        }
+    }
+}
+
+impl FastaSequence for RnaStrand {
+    type Residue = rna::Nucleotide;
+
+    fn residues(&self) -> Vec<Self::Residue> {
+        self.0.clone()
+    }
+
+    fn from_residues(residues: Vec<Self::Residue>) -> Self {
+        RnaStrand(residues)
+    }
+}
+
+impl FastaSequence for AminoAcidChain {
+    type Residue = AminoAcid;
+
+    fn residues(&self) -> Vec<Self::Residue> {
+        self.0.clone()
+    }
+
+    fn from_residues(residues: Vec<Self::Residue>) -> Self {
+        AminoAcidChain(residues)
+    }
+}
+
+// Parses a bare sequence such as "ATTAGA", which is handy for writing genomes by hand
+pub fn parse_sequence<T: FastaSequence>(text: &str) -> Result<T, FastaError> {
+    let mut residues = Vec::new();
+    push_residues::<T>(text, 1, &mut residues)?;
+    Ok(T::from_residues(residues))
+}
+
+fn push_residues<T: FastaSequence>(
+    text: &str,
+    line: usize,
+    residues: &mut Vec<T::Residue>,
+) -> Result<(), FastaError> {
+    for (column, character) in text.chars().enumerate() {
+        if character.is_whitespace() {
+            continue;
+        }
+        let residue =
+            T::Residue::try_from(character).map_err(|character| FastaError::InvalidCharacter {
+                line,
+                column: column + 1,
+                character,
+            })?;
+        residues.push(residue);
+    }
+    Ok(())
+}
+
+/**
+ * Reads every record from FASTA text.  Blank lines and ';' comment lines are skipped, and
+ * sequences may be split over any number of lines.
+ */
+pub fn read_fasta<T: FastaSequence, R: BufRead>(
+    reader: R,
+) -> Result<Vec<FastaRecord<T>>, FastaError> {
+    let mut records = Vec::new();
+    let mut current: Option<(FastaHeader, Vec<T::Residue>)> = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        let trimmed = line.trim_end();
+
+        if let Some(header) = trimmed.strip_prefix('>') {
+            if let Some((header, residues)) = current.take() {
+                records.push(FastaRecord {
+                    header,
+                    sequence: T::from_residues(residues),
+                });
+            }
+            let header =
+                FastaHeader::parse(header).ok_or(FastaError::EmptyId { line: line_number })?;
+            current = Some((header, Vec::new()));
+        } else if trimmed.trim_start().is_empty() || trimmed.starts_with(';') {
+            continue;
+        } else {
+            let Some((_, residues)) = current.as_mut() else {
+                return Err(FastaError::MissingHeader { line: line_number });
+            };
+            push_residues::<T>(trimmed, line_number, residues)?;
+        }
+    }
+
+    if let Some((header, residues)) = current {
+        records.push(FastaRecord {
+            header,
+            sequence: T::from_residues(residues),
+        });
+    }
+
+    Ok(records)
+}
+
+pub fn write_fasta<T: FastaSequence, W: Write>(
+    mut writer: W,
+    records: &[FastaRecord<T>],
+) -> io::Result<()> {
+    for record in records {
+        record.header.check_description()?;
+        writeln!(writer, ">{}", record.header)?;
+        let residues: Vec<char> = record
+            .sequence
+            .residues()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        for line in residues.chunks(LINE_WIDTH) {
+            writeln!(writer, "{}", line.iter().collect::<String>())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_fasta_file<T: FastaSequence>(
+    path: impl AsRef<Path>,
+) -> Result<Vec<FastaRecord<T>>, FastaError> {
+    read_fasta(BufReader::new(File::open(path)?))
+}
+
+pub fn write_fasta_file<T: FastaSequence>(
+    path: impl AsRef<Path>,
+    records: &[FastaRecord<T>],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_fasta(&mut writer, records)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use gene_traits::{amino_acid::AminoAcid, dna, rna};
+
+    use super::{
+        FastaError, FastaHeader, FastaRecord, LINE_WIDTH, parse_sequence, read_fasta, write_fasta,
+    };
+    use crate::components::{
+        amino_acid_reader::AminoAcidChain,
+        gene_reader::{Genome, RnaStrand},
+    };
+
+    #[test]
+    fn reads_multiple_records_with_metadata() {
+        let text = "\
+;a comment
+>first generation=3 parent=root a small genome
+ATTAGA
+atta
+
+>second
+GGGC
+";
+        let records = read_fasta::<Genome, _>(text.as_bytes()).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].header.id, "first");
+        assert_eq!(records[0].header.get("generation"), Some("3"));
+        assert_eq!(records[0].header.get("parent"), Some("root"));
+        assert_eq!(records[0].header.description, "a small genome");
+        assert_eq!(
+            records[0].sequence.sequence,
+            parse_sequence::<Genome>("ATTAGAATTA").unwrap().sequence
+        );
+        assert_eq!(records[1].header, FastaHeader::new("second"));
+        assert_eq!(
+            records[1].sequence.sequence.to_vec(),
+            vec![
+                dna::Nucleotide::G,
+                dna::Nucleotide::G,
+                dna::Nucleotide::G,
+                dna::Nucleotide::C,
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_every_sequence_type() {
+        let header = FastaHeader {
+            id: "gene".to_string(),
+            metadata: vec![("organism".to_string(), "7".to_string())],
+            // Words with '=' after the first plain word belong to the description
+            description: "round trip at rate=0.01".to_string(),
+        };
+
+        let genome = vec![FastaRecord {
+            header: header.clone(),
+            sequence: parse_sequence::<Genome>(&"ACGT".repeat(40)).unwrap(),
+        }];
+        let mut written = Vec::new();
+        write_fasta(&mut written, &genome).unwrap();
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(text.lines().all(|line| line.len() <= LINE_WIDTH + 1));
+        let read = read_fasta::<Genome, _>(written.as_slice()).unwrap();
+        assert_eq!(read[0].header, header);
+        assert_eq!(read[0].sequence.sequence, genome[0].sequence.sequence);
+
+        let strand = vec![FastaRecord {
+            header: header.clone(),
+            sequence: RnaStrand(vec![rna::Nucleotide::U, rna::Nucleotide::G]),
+        }];
+        let mut written = Vec::new();
+        write_fasta(&mut written, &strand).unwrap();
+        assert_eq!(
+            read_fasta::<RnaStrand, _>(written.as_slice()).unwrap(),
+            strand
+        );
+
+        let chain = vec![FastaRecord {
+            header,
+            sequence: AminoAcidChain(vec![AminoAcid::W, AminoAcid::UNKNOWN, AminoAcid::A]),
+        }];
+        let mut written = Vec::new();
+        write_fasta(&mut written, &chain).unwrap();
+        assert_eq!(
+            String::from_utf8(written.clone()).unwrap(),
+            ">gene organism=7 round trip at rate=0.01\nWXA\n"
+        );
+        let read = read_fasta::<AminoAcidChain, _>(written.as_slice()).unwrap();
+        assert_eq!(read[0].sequence.0, chain[0].sequence.0);
+    }
+
+    #[test]
+    fn descriptions_that_read_as_metadata_are_refused() {
+        // Nothing marks where the metadata ends, so this description can't be written
+        let records = vec![FastaRecord {
+            header: FastaHeader {
+                id: "child".to_string(),
+                metadata: Vec::new(),
+                description: "rate=0.01 mutations".to_string(),
+            },
+            sequence: parse_sequence::<Genome>("ACGT").unwrap(),
+        }];
+        let error = write_fasta(&mut Vec::new(), &records).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn reports_invalid_characters() {
+        let text = ">genome\nACGT\nACUT\n";
+        match read_fasta::<Genome, _>(text.as_bytes()) {
+            Err(FastaError::InvalidCharacter {
+                line,
+                column,
+                character,
+            }) => {
+                assert_eq!((line, column, character), (3, 3, 'U'));
+            }
+            other => panic!(
+                "Expected an invalid character, got {:?}",
+                other.map(|r| r.len())
+            ),
+        }
+
+        assert!(matches!(
+            parse_sequence::<RnaStrand>("ACGT"),
+            Err(FastaError::InvalidCharacter { character: 'T', .. })
+        ));
+    }
+
+    #[test]
+    fn reports_missing_header_and_empty_id() {
+        assert!(matches!(
+            read_fasta::<Genome, _>("ACGT\n".as_bytes()),
+            Err(FastaError::MissingHeader { line: 1 })
+        ));
+        assert!(matches!(
+            read_fasta::<Genome, _>(">\nACGT\n".as_bytes()),
+            Err(FastaError::EmptyId { line: 1 })
+        ));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod assembler;
+pub mod component_register;
+pub mod components;
+pub mod config;
+pub mod disassembler;
+pub mod fasta;
+pub mod systems;
+
+pub use crate::component_register::ComponentRegister;
+pub use crate::components::*;
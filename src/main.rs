@@ -1,35 +1,42 @@
-use bevy::prelude::*;
-use components::accumulator::Accumulator;
-use evalexpr::build_operator_tree;
-
-mod component_register;
-mod components;
-mod systems;
+mod cli;
 
-use crate::{
-    component_register::ComponentRegister,
-    config::PROMOTER_SIZE,
-    systems::neuron_updates::{accumulator_buildup, receptor, update_neuron, update_synapse},
+use articial_life::{
+    component_register::HeaderReport,
+    components::gene_reader::{annotate_genome, parse_attached_genome},
+    systems::{
+        neuron_updates::{
+            SimulationClock, advance_clock, update_neuron, update_synapse,
+        },
+        ribosome::parse_amino_acid_strand,
+        translation::{ActiveGeneticCode, translate_rna_strand},
+    },
+};
+use articial_life::{
+    components::*,
+    systems::neurotransmitter_updates::{NeurotransmitterPlugins, NeurotransmitterSet},
 };
-use crate::{components::*, systems::neurotransmitter_updates::update_dopamine};
+use articial_life::components::accumulator::Accumulator;
+use bevy::prelude::*;
+use evalexpr::build_operator_tree;
+use gene_traits::amino_acid::GeneticCode;
 
-mod config;
+// Genes whose headers are identical can never be told apart, so refuse to start with them
+fn validate_headers() {
+    let report = HeaderReport::registered();
+    println!("{}", report);
+    if let Err(collisions) = report.validate() {
+        panic!("Registered gene headers collide: {:?}", collisions);
+    }
+}
 
 fn startup(mut commands: Commands) {
-    for c in inventory::iter::<ComponentRegister<PROMOTER_SIZE>> {
-        println!("{:?}", c);
-    }
     let activators = [0]
         .map(|_| {
             commands
                 .spawn((
-                    Neuron {
-                        dopamine: 100,
-                        ..default()
-                    },
-                    Activation {
-                        activation: build_operator_tree("dopamine >= 100").expect("This is a valid expression"),
-                    },
+                    Neuron::default().with_level::<Dopamine>(100),
+                    Activation::new(build_operator_tree("dopamine >= 100").expect("This is a valid expression"))
+                        .expect("Activations can compare dopamine"),
                     Synapse { active: false },
                     Accumulator::<Dopamine>::new(100, 5),
                 ))
@@ -45,14 +52,10 @@ fn startup(mut commands: Commands) {
         .id();
 
     commands.spawn((
-        Neuron {
-            dopamine_receptors: vec![dopamine_receptor],
-            ..default()
-        },
+        Neuron::default().with_receptors::<Dopamine>(vec![dopamine_receptor]),
         Synapse { active: false },
-        Activation {
-            activation: build_operator_tree("dopamine >= 100").expect("This is a valid expression"),
-        },
+        Activation::new(build_operator_tree("dopamine >= 100").expect("This is a valid expression"))
+            .expect("Activations can compare dopamine"),
         UpdateFunction::default(),
     ));
 }
@@ -67,27 +70,57 @@ fn startup(mut commands: Commands) {
 //
 // fn update_internal_state<T>()
 
-//#[derive(Component, HashedTypeDef)]
-//struct DopamineAccumulator(Accumulator<Dopamine>);
-//
-//impl From<Accumulator<Dopamine>> for DopamineAccumulator {
-//    fn from(value: Accumulator<Dopamine>) -> Self {
-//        Self(value)
-//    }
-//}
+// Set GENETIC_CODE to a codon table file to translate genes with something other than the
+// standard code
+fn genetic_code() -> GeneticCode {
+    match std::env::var("GENETIC_CODE") {
+        Ok(path) => GeneticCode::load(&path)
+            .unwrap_or_else(|e| panic!("Couldn't load the genetic code from {}: {}", path, e)),
+        Err(_) => GeneticCode::STANDARD,
+    }
+}
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args) {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_systems(Startup, startup)
+        .insert_resource(ActiveGeneticCode(genetic_code()))
+        .init_resource::<SimulationClock>()
+        .add_systems(Startup, (validate_headers, startup).chain())
+        .add_systems(
+            Update,
+            (
+                annotate_genome,
+                parse_attached_genome,
+                translate_rna_strand,
+                parse_amino_acid_strand,
+            )
+                .chain(),
+        )
+        .add_plugins(NeurotransmitterPlugins)
         .add_systems(
             Update,
             (
-                receptor::<Dopamine>,
-                accumulator_buildup::<Dopamine>,
-                update_synapse,
-                update_dopamine,
-                update_neuron,
+                // Neurons fire on what built up and the receptors collect what they released
+                update_synapse
+                    .after(NeurotransmitterSet::Buildup)
+                    .before(NeurotransmitterSet::Receptors),
+                // Update genes see what was taken up before it is cleared
+                update_neuron
+                    .after(NeurotransmitterSet::Uptake)
+                    .before(NeurotransmitterSet::Clearance),
+                advance_clock
+                    .after(NeurotransmitterSet::Clearance)
+                    .after(update_synapse)
+                    .after(update_neuron),
             ),
         )
         .run();
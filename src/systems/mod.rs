@@ -1,3 +1,4 @@
 pub mod neuron_updates;
 pub mod neurotransmitter_updates;
 pub mod ribosome;
+pub mod translation;
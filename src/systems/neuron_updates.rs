@@ -1,51 +1,112 @@
-use bevy::ecs::system::Query;
+use bevy::ecs::{
+    entity::Entity,
+    resource::Resource,
+    system::{Query, Res, ResMut, SystemParam},
+};
+use gene_traits::mutation::seeded_rng;
+use rand::Rng;
 use std::fmt::Debug;
 
 use crate::components::{
-    accumulator::Accumulator, Activation, Neuron, NeuronUpdater, Receptor, Synapse, UpdateFunction
+    accumulator::Accumulator,
+    expr_variables::{ExprVariables, RANDOM_RANGE},
+    neurotransmitters::{AccumulatorQueries, ReceptorQueries, built_up, received},
+    Activation, Dopamine, Neuron, Receptor, Synapse, UpdateFunction,
 };
 
+// Counts the ticks of the simulation.  The seed makes the random draws of expression genes
+// reproducible, the same seed gives every neuron the same draws on every run.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct SimulationClock {
+    pub tick: u64,
+    pub seed: u64,
+}
+
+impl SimulationClock {
+    // Draws are made from the seed, the tick and the neuron, so they don't depend on the order
+    // neurons are evaluated in
+    pub fn random_draw(&self, neuron: Entity) -> u32 {
+        seeded_rng((self.seed, self.tick, neuron.to_bits())).random_range(0..RANDOM_RANGE)
+    }
+}
+
+pub fn advance_clock(mut clock: ResMut<SimulationClock>) {
+    clock.tick += 1;
+}
+
+// Everything besides the neuron itself that expression genes read
+#[derive(SystemParam)]
+pub struct ExprInputs<'w, 's> {
+    receptors: ReceptorQueries<'w, 's>,
+    accumulators: AccumulatorQueries<'w, 's>,
+    clock: Option<Res<'w, SimulationClock>>,
+}
+
+impl ExprInputs<'_, '_> {
+    pub fn variables(&self, entity: Entity, neuron: &Neuron) -> ExprVariables {
+        let clock = self.clock.as_deref().copied().unwrap_or_default();
+
+        ExprVariables {
+            levels: neuron.levels,
+            received: received(&self.receptors, neuron),
+            built_up: built_up(&self.accumulators, entity),
+            ticks_since_fire: neuron.ticks_since_fire,
+            time: clock.tick,
+            random: clock.random_draw(entity),
+        }
+    }
+}
+
 // The neuron should update in a way that neurotransmitters are both accepted by receptors, and degraded by some internal function
-pub fn update_neuron(mut neurons: Query<(&UpdateFunction, &mut Neuron)>) {
-    for (update, mut neuron) in neurons.iter_mut() {
-        update.eval(&mut neuron); // This has to be some arbitrary function that gets modified by genetics
+pub fn update_neuron(
+    mut neurons: Query<(Entity, &UpdateFunction, &mut Neuron)>,
+    inputs: ExprInputs,
+) {
+    for (entity, update, mut neuron) in neurons.iter_mut() {
+        let variables = inputs.variables(entity, &neuron);
+        update.eval(&mut neuron, &variables); // This has to be some arbitrary function that gets modified by genetics
     }
 }
 
-pub fn update_synapse(mut query: Query<(&mut Neuron, &mut Synapse, &Activation)>) {
-    for (mut neuron, mut synapse, activation) in query.iter_mut() {
+pub fn update_synapse(
+    mut query: Query<(Entity, &mut Neuron, &mut Synapse, &Activation)>,
+    inputs: ExprInputs,
+) {
+    for (entity, mut neuron, mut synapse, activation) in query.iter_mut() {
         println!("Updating synapse");
-        synapse.active = activation.get_activation(&mut neuron);
+        synapse.active = activation.get_activation(&inputs.variables(entity, &neuron));
         if synapse.active {
-            neuron.dopamine = 0;
+            *neuron.level_mut::<Dopamine>() = 0;
+            neuron.ticks_since_fire = 0;
+        } else {
+            neuron.ticks_since_fire = neuron.ticks_since_fire.saturating_add(1);
         }
         println!("Synapse is active? {}", synapse.active);
     }
 }
 
+// Fills receptors with what the accumulators they are connected to released, uptake then passes it
+// on to the neurons the receptors belong to
 pub fn receptor<T>(
     mut accumulators: Query<(&Synapse, &mut Accumulator<T>)>,
-    mut receptors: Query<(&mut Receptor<T>, &mut Neuron)>,
+    mut receptors: Query<&mut Receptor<T>>,
 ) where
     T: Send + 'static,
     T: Sync,
     T: Debug,
-    T: NeuronUpdater
 {
     // Well... this works, kinda, but it's not physically correct.  Need a way to "attract"
     // neurotransmitters to receptors.  This just assumes all neurotransmitters get taken up by all
     // receptors.  This is not reality, but kind of works for now.  Only for now, replace this.
     // TODO: Make this more physically accurate.
-    for (mut receptor, mut neuron) in receptors.iter_mut() {
-        let activation_sum: u32 = receptor
-            .connected_accumulators
-            .iter()
-            .map(|&e| accumulators.get(e).unwrap())
+    for mut receptor in receptors.iter_mut() {
+        // Accumulators that have gone away, or hold another neurotransmitter, don't release anything
+        let activation_sum: u32 = accumulators
+            .iter_many(&receptor.connected_accumulators)
             .filter(|(s, _)| s.active)
             .map(|(_, a)| a.level)
             .sum();
         receptor.level = activation_sum;
-        T::update(receptor.level, &mut neuron);
         println!("receptor level = {}", receptor.level);
     }
 
@@ -65,3 +126,104 @@ where
         accumulator.level += accumulator.buildup_rate;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::{App, Update},
+        ecs::schedule::IntoScheduleConfigs,
+    };
+    use evalexpr::{DefaultNumericTypes, build_operator_tree};
+
+    use super::{SimulationClock, advance_clock, update_synapse};
+    use crate::components::{
+        Activation, Dopamine, Neuron, Receptor, Serotonin, Synapse, accumulator::Accumulator,
+    };
+
+    fn activation(expr: &str) -> Activation {
+        Activation::new(build_operator_tree::<DefaultNumericTypes>(expr).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn refractory_neurons_wait_between_fires() {
+        let mut app = App::new();
+        app.init_resource::<SimulationClock>();
+        app.add_systems(Update, (update_synapse, advance_clock).chain());
+        let neuron = app
+            .world_mut()
+            .spawn((
+                Neuron::default(),
+                Synapse::default(),
+                activation("ticks_since_fire >= 2 && time > 0"),
+            ))
+            .id();
+
+        let mut fired = Vec::new();
+        for _ in 0..8 {
+            app.update();
+            fired.push(app.world().get::<Synapse>(neuron).unwrap().active);
+        }
+
+        assert_eq!(
+            fired,
+            [false, false, true, false, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn receptors_and_accumulators_are_readable() {
+        let mut app = App::new();
+        app.add_systems(Update, update_synapse);
+        let receptor = app
+            .world_mut()
+            .spawn(Receptor::<Serotonin> {
+                level: 7,
+                ..Default::default()
+            })
+            .id();
+        let neuron = app
+            .world_mut()
+            .spawn((
+                Neuron::default().with_receptors::<Serotonin>(vec![receptor]),
+                Synapse::default(),
+                Accumulator::<Dopamine>::new(3, 1),
+                activation(
+                    "serotonin_receptor == 7 && dopamine_accumulator == 3 && serotonin_accumulator == 0",
+                ),
+            ))
+            .id();
+
+        app.update();
+
+        assert!(app.world().get::<Synapse>(neuron).unwrap().active);
+    }
+
+    #[test]
+    fn random_draws_follow_the_seed() {
+        let draws = |seed: u64| {
+            let mut app = App::new();
+            app.insert_resource(SimulationClock { tick: 0, seed });
+            app.add_systems(Update, (update_synapse, advance_clock).chain());
+            let neuron = app
+                .world_mut()
+                .spawn((
+                    Neuron::default(),
+                    Synapse::default(),
+                    activation("random < 500"),
+                ))
+                .id();
+            (0..64)
+                .map(|_| {
+                    app.update();
+                    app.world().get::<Synapse>(neuron).unwrap().active
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(draws(3), draws(3));
+        assert_ne!(draws(3), draws(4));
+        // Roughly half of the draws are below 500
+        let fired = draws(3).iter().filter(|&&active| active).count();
+        assert!((16..48).contains(&fired), "{}", fired);
+    }
+}
@@ -1,18 +1,336 @@
-use bevy::ecs::system::Query;
+use std::fmt::Debug;
+use std::marker::PhantomData;
 
-use crate::components::{Dopamine, Neuron, Receptor};
+use bevy::app::{App, Plugin, Update};
+use bevy::ecs::{
+    resource::Resource,
+    schedule::{IntoScheduleConfigs, SystemSet},
+    system::{Query, Res},
+};
 
-// TODO: This should really be made into a macro, all neurotransmitters will do exactly the same things
-// just updating different fields inside the neuron
-pub fn update_dopamine(mut neurons: Query<&mut Neuron>, receptors: Query<&Receptor<Dopamine>>) {
+use crate::components::{
+    Neuron, Neurotransmitter, Receptor,
+    clearance::{Clearance, ClearanceParams},
+    neurotransmitters::{NeurotransmitterVisitor, for_each_neurotransmitter},
+};
+use crate::systems::neuron_updates::{accumulator_buildup, receptor};
+
+/**
+ * The order the systems of every neurotransmitter run in within a tick.  Systems that don't belong
+ * to a neurotransmitter, like firing synapses and update genes, are placed between these.
+ */
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeurotransmitterSet {
+    // Accumulators build up what their neuron will release
+    Buildup,
+    // Receptors collect what firing neurons released
+    Receptors,
+    // Neurons take in what their receptors collected
+    Uptake,
+    // Levels fall back towards rest
+    Clearance,
+}
+
+// Adds what each neuron's receptors collected to its level.  Receptors that have gone away don't
+// add anything.
+pub fn uptake<T>(mut neurons: Query<&mut Neuron>, receptors: Query<&Receptor<T>>)
+where
+    T: Neurotransmitter,
+{
     for mut neuron in neurons.iter_mut() {
-        neuron.dopamine += neuron
-            .dopamine_receptors
-            .iter()
-            .map(|&e| receptors.get(e).unwrap())
-            .map(|d| d.level)
-            .sum::<u32>();
-        println!("Dopamine level in neuron: {:?}", neuron.dopamine);
-        // There also has to be some internal buildup of the various neurotransmitters
+        let received = receptors
+            .iter_many(neuron.receptors::<T>())
+            .map(|r| r.level)
+            .fold(0, u32::saturating_add);
+        // Levels stop at u32::MAX, the same as update genes leave them
+        let level = neuron.level_mut::<T>();
+        *level = level.saturating_add(received);
+    }
+}
+
+// How neurons without a Clearance gene of their own clear a neurotransmitter
+#[derive(Resource, Debug)]
+pub struct DefaultClearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    pub params: ClearanceParams,
+    pub _phantom: PhantomData<T>,
+}
+
+impl<T> DefaultClearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    pub fn new(params: ClearanceParams) -> Self {
+        Self {
+            params,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for DefaultClearance<T>
+where
+    T: Send,
+    T: Sync,
+    T: Debug,
+{
+    fn default() -> Self {
+        Self::new(ClearanceParams::default())
+    }
+}
+
+// Brings every neuron's level back towards rest, using its own gene when it has one
+pub fn clearance<T>(
+    mut neurons: Query<(&mut Neuron, Option<&Clearance<T>>)>,
+    defaults: Option<Res<DefaultClearance<T>>>,
+) where
+    T: Neurotransmitter,
+{
+    let default = defaults.map_or_else(ClearanceParams::default, |d| d.params);
+    for (mut neuron, own) in neurons.iter_mut() {
+        let params = own.map_or(default, |c| c.params);
+        let level = neuron.level_mut::<T>();
+        *level -= params.cleared(*level);
+    }
+}
+
+// Simulates one neurotransmitter: builds up its accumulators, fills its receptors, passes what they
+// collected on to the neurons and clears it again
+pub struct NeurotransmitterPlugin<T> {
+    pub _phantom: PhantomData<T>,
+}
+
+impl<T> Default for NeurotransmitterPlugin<T> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Plugin for NeurotransmitterPlugin<T>
+where
+    T: Neurotransmitter,
+{
+    fn build(&self, app: &mut App) {
+        use NeurotransmitterSet::*;
+
+        app.configure_sets(Update, (Buildup, Receptors, Uptake, Clearance).chain())
+            .init_resource::<DefaultClearance<T>>()
+            .add_systems(
+                Update,
+                (
+                    accumulator_buildup::<T>.in_set(Buildup),
+                    receptor::<T>.in_set(Receptors),
+                    uptake::<T>.in_set(Uptake),
+                    clearance::<T>.in_set(Clearance),
+                ),
+            );
+    }
+}
+
+// A NeurotransmitterPlugin for every listed neurotransmitter
+pub struct NeurotransmitterPlugins;
+
+impl Plugin for NeurotransmitterPlugins {
+    fn build(&self, app: &mut App) {
+        struct AddPlugin<'a>(&'a mut App);
+
+        impl NeurotransmitterVisitor for AddPlugin<'_> {
+            fn visit<T: Neurotransmitter>(&mut self) {
+                self.0.add_plugins(NeurotransmitterPlugin::<T>::default());
+            }
+        }
+
+        for_each_neurotransmitter(&mut AddPlugin(app));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+
+    use super::{DefaultClearance, NeurotransmitterPlugins, clearance, uptake};
+    use crate::components::{
+        Dopamine, Neuron, Neurotransmitter, Norepinephrine, Receptor, Serotonin, Synapse,
+        accumulator::Accumulator,
+        clearance::{Clearance, ClearanceParams},
+    };
+    use crate::systems::neuron_updates::receptor;
+
+    #[test]
+    fn genes_override_the_default_clearance() {
+        let degrade = |degradation| ClearanceParams {
+            resting_level: 0,
+            decay: 0,
+            reuptake_max: 0,
+            reuptake_half: 0,
+            degradation,
+        };
+        let mut app = App::new();
+        app.insert_resource(DefaultClearance::<Dopamine>::new(degrade(10)));
+        app.add_systems(Update, clearance::<Dopamine>);
+        let neuron = || {
+            Neuron::default()
+                .with_level::<Dopamine>(100)
+                .with_level::<Serotonin>(100)
+        };
+        let default = app.world_mut().spawn(neuron()).id();
+        let own = app
+            .world_mut()
+            .spawn((neuron(), Clearance::<Dopamine>::new(degrade(3))))
+            .id();
+        let resting = app
+            .world_mut()
+            .spawn((
+                Neuron::default().with_level::<Dopamine>(5),
+                // Only the dopamine clearance runs, so this one changes nothing
+                Clearance::<Serotonin>::new(degrade(50)),
+            ))
+            .id();
+
+        for _ in 0..2 {
+            app.update();
+        }
+
+        let neuron = |entity| app.world().get::<Neuron>(entity).unwrap();
+        assert_eq!(neuron(default).level::<Dopamine>(), 80);
+        assert_eq!(neuron(default).level::<Serotonin>(), 100);
+        assert_eq!(neuron(own).level::<Dopamine>(), 94);
+        assert_eq!(neuron(resting).level::<Dopamine>(), 0);
+    }
+
+    #[test]
+    fn despawned_receptors_are_skipped() {
+        let mut app = App::new();
+        app.add_systems(Update, uptake::<Dopamine>);
+        let receptor = |level| Receptor::<Dopamine> {
+            level,
+            ..Default::default()
+        };
+        let kept = app.world_mut().spawn(receptor(5)).id();
+        let gone = app.world_mut().spawn(receptor(7)).id();
+        app.world_mut().despawn(gone);
+        let neuron = app
+            .world_mut()
+            .spawn(Neuron::default().with_receptors::<Dopamine>(vec![kept, gone]))
+            .id();
+
+        app.update();
+
+        let neuron = app.world().get::<Neuron>(neuron).unwrap();
+        assert_eq!(neuron.level::<Dopamine>(), 5);
+    }
+
+    #[test]
+    fn receptors_skip_missing_accumulators() {
+        let mut app = App::new();
+        app.add_systems(Update, receptor::<Dopamine>);
+        let firing = app
+            .world_mut()
+            .spawn((Synapse { active: true }, Accumulator::<Dopamine>::new(4, 0)))
+            .id();
+        let other = app
+            .world_mut()
+            .spawn((
+                Synapse { active: true },
+                Accumulator::<Serotonin>::new(9, 0),
+            ))
+            .id();
+        let gone = app
+            .world_mut()
+            .spawn((Synapse { active: true }, Accumulator::<Dopamine>::new(7, 0)))
+            .id();
+        app.world_mut().despawn(gone);
+        let collecting = app
+            .world_mut()
+            .spawn(Receptor::<Dopamine> {
+                connected_accumulators: vec![gone, other, firing],
+                ..Default::default()
+            })
+            .id();
+
+        app.update();
+
+        assert_eq!(
+            app.world()
+                .get::<Receptor<Dopamine>>(collecting)
+                .unwrap()
+                .level,
+            4
+        );
+    }
+
+    #[test]
+    fn uptake_saturates() {
+        let mut app = App::new();
+        app.add_systems(Update, uptake::<Dopamine>);
+        let receptors = [u32::MAX, 5].map(|level| {
+            app.world_mut()
+                .spawn(Receptor::<Dopamine> {
+                    level,
+                    ..Default::default()
+                })
+                .id()
+        });
+        let neuron = app
+            .world_mut()
+            .spawn(
+                Neuron::default()
+                    .with_level::<Dopamine>(u32::MAX - 1)
+                    .with_receptors::<Dopamine>(receptors.to_vec()),
+            )
+            .id();
+
+        app.update();
+
+        let neuron = app.world().get::<Neuron>(neuron).unwrap();
+        assert_eq!(neuron.level::<Dopamine>(), u32::MAX);
+    }
+
+    // A firing neuron releases into a receptor of another, which takes it up and clears a little
+    fn released_and_taken_up<T: Neurotransmitter>() -> u32 {
+        let mut app = App::new();
+        app.insert_resource(DefaultClearance::<T>::new(ClearanceParams {
+            resting_level: 0,
+            decay: 0,
+            reuptake_max: 0,
+            reuptake_half: 0,
+            degradation: 1,
+        }));
+        app.add_plugins(NeurotransmitterPlugins);
+        let firing = app
+            .world_mut()
+            .spawn((Synapse { active: true }, Accumulator::<T>::new(0, 10)))
+            .id();
+        let receptor = app
+            .world_mut()
+            .spawn(Receptor::<T> {
+                level: 0,
+                connected_accumulators: vec![firing],
+                ..Default::default()
+            })
+            .id();
+        let neuron = app
+            .world_mut()
+            .spawn(Neuron::default().with_receptors::<T>(vec![receptor]))
+            .id();
+
+        app.update();
+
+        app.world().get::<Neuron>(neuron).unwrap().level::<T>()
+    }
+
+    #[test]
+    fn every_neurotransmitter_is_simulated() {
+        assert_eq!(released_and_taken_up::<Dopamine>(), 9);
+        assert_eq!(released_and_taken_up::<Serotonin>(), 9);
+        assert_eq!(released_and_taken_up::<Norepinephrine>(), 9);
     }
 }
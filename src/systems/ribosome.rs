@@ -1,56 +1,124 @@
 use bevy::ecs::{
-    system::{Commands, Query},
+    entity::Entity,
+    hierarchy::ChildOf,
+    query::With,
+    system::{Commands, EntityCommands, Query},
 };
+use gene_traits::amino_acid::AminoAcid;
 use generic_levenshtein;
 use crate::{
-    component_register::ComponentRegister,
-    components::amino_acid_reader::AminoAcidChain,
+    component_register::{ComponentRegister, HeaderTable},
+    components::amino_acid_reader::{AminoAcidChain, AminoAcidReader, GeneOutcome, ParseReport},
     config::PROMOTER_SIZE,
 };
 
-pub fn parse_amino_acid_strand(
-    query: Query<&AminoAcidChain>,
-    mut commands: Commands,
-) {
-    for acid in query.iter() {
-        let mut strand_index = 0;
-        let mut e = commands.spawn_empty();
-        while strand_index < acid.len() {
-            let slice = &acid[strand_index..];
-            let mut strand_window = slice.windows(PROMOTER_SIZE).enumerate();
-            let mut distance = PROMOTER_SIZE + 1;
-            let mut parser = None;
-            let mut window_idx = 0;
-            // Search through the strand for a known component header
-            while let Some((idx, current_window)) = strand_window.next() {
-                for c in inventory::iter::<ComponentRegister<PROMOTER_SIZE>>::iter() {
-                    let current_distance = generic_levenshtein::distance(current_window, &c.header);
-                    if (current_distance < PROMOTER_SIZE / 2) && (current_distance < distance) {
-                        distance = current_distance;
-                        parser = Some(c.parser);
-                        window_idx = idx;
-                    }
-                    // Found an exact match, bail
-                    if distance == 0 {
-                        break;
-                    }
-                }
+// A registered header found in a strand of amino acids
+#[derive(Debug, Clone, Copy)]
+pub struct HeaderMatch<const N: usize = PROMOTER_SIZE> {
+    pub register: &'static ComponentRegister<N>,
+    pub distance: usize,
+    // Where the header starts, relative to the strand that was searched
+    pub index: usize,
+}
+
+// Searches through the strand for any of the registered component headers
+pub fn find_header(strand: &[AminoAcid]) -> Option<HeaderMatch> {
+    find_header_among(strand, HeaderTable::registered())
+}
+
+/**
+ * Searches through the strand for one of the given headers.  Headers count as found when they are
+ * less than N / 2 edits away, and the closest one wins.  On a tie the header that comes first in
+ * the strand wins.
+ */
+pub fn find_header_among<const N: usize>(
+    strand: &[AminoAcid],
+    headers: &HeaderTable<N>,
+) -> Option<HeaderMatch<N>> {
+    let mut best: Option<HeaderMatch<N>> = None;
+    for (index, current_window) in strand.windows(N).enumerate() {
+        for (register, header) in headers.iter() {
+            let distance = generic_levenshtein::distance(current_window, header);
+            if distance < N / 2 && best.is_none_or(|best| distance < best.distance) {
+                best = Some(HeaderMatch {
+                    register,
+                    distance,
+                    index,
+                });
             }
-            if parser.is_some() {
-                strand_index = window_idx * PROMOTER_SIZE + PROMOTER_SIZE;
-                strand_index += parser.unwrap()(&acid[strand_index..], e.reborrow());
-            } else {
-                break;
+            // Found an exact match, nothing later can beat it
+            if best.is_some_and(|best| best.distance == 0) {
+                return best;
             }
         }
     }
+    best
+}
+
+/**
+ * Builds the component of every gene found in the chain onto the entity.  Genes that fail to parse
+ * are recorded in the report and reading carries on after them.
+ */
+pub fn read_chain<const N: usize>(
+    acid: &[AminoAcid],
+    headers: &HeaderTable<N>,
+    mut e: EntityCommands,
+) -> ParseReport {
+    let mut strand_index = 0;
+    let mut report = ParseReport::default();
+    while strand_index < acid.len() {
+        if let Some(found) = find_header_among(&acid[strand_index..], headers) {
+            // The header index is relative to the current slice
+            let header_start = strand_index + found.index;
+            let payload_start = header_start + N;
+            let result = (found.register.parser)(&acid[payload_start..], e.reborrow());
+            let consumed = match &result {
+                Ok(consumed) => *consumed,
+                Err(error) => error.consumed,
+            };
+            strand_index = payload_start + consumed;
+            report.genes.push(GeneOutcome {
+                id: found.register.id,
+                type_str: found.register.type_str,
+                distance: found.distance,
+                header: header_start..payload_start,
+                consumed: payload_start..strand_index,
+                error: result.err().map(|error| error.message),
+            });
+        } else {
+            break;
+        }
+    }
+    report
+}
+
+pub fn parse_amino_acid_strand(
+    query: Query<(Entity, &AminoAcidChain, Option<&ChildOf>), With<AminoAcidReader>>,
+    mut commands: Commands,
+) {
+    for (chain_entity, acid, organism) in query.iter() {
+        // Chains translated from a genome belong to the organism that carries it, loose chains get
+        // an entity of their own.
+        let e = match organism {
+            Some(child_of) => commands.entity(child_of.parent()),
+            None => commands.spawn_empty(),
+        };
+        let report = read_chain(acid, HeaderTable::registered(), e);
+        // The chain has been read, don't parse it again next frame
+        commands
+            .entity(chain_entity)
+            .remove::<AminoAcidReader>()
+            .insert(report);
+    }
 }
 
 #[macro_export]
 macro_rules! amino_acid_header {
     ($ty:ty) => {{
-        const HASH: u128 = get_hash::<$ty>();
-        get_header::<4, 12, HASH>()
+        let id = <$ty as gene_traits::header::Gene>::ID;
+        $crate::component_register::HeaderTable::registered()
+            .header_of(id)
+            .unwrap_or_else(|| panic!("{} is not a registered gene", id))
     }}
 }
 
@@ -60,35 +128,33 @@ mod test {
         app::{App, Update},
         ecs::world::World,
     };
-    use gene_traits::amino_acid::AminoAcid;
-    use gene_traits::dna::{get_hash, get_header};
+    use gene_traits::{
+        amino_acid::AminoAcid,
+        header::{Gene, GeneId},
+    };
+    use bevy::ecs::{system::EntityCommands, world::EntityRef};
     use crate::{
+        component_register::{ComponentRegister, GeneParseError, HeaderTable},
         components::{
             Norepinephrine,
             accumulator::Accumulator,
-            amino_acid_reader::{AminoAcidChain, AminoAcidReader},
+            amino_acid_reader::{AminoAcidChain, AminoAcidReader, ParseReport},
         },
-        systems::ribosome::parse_amino_acid_strand,
+        config::{HEADER_MIN_DISTANCE, PROMOTER_SIZE},
+        systems::ribosome::{find_header_among, parse_amino_acid_strand, read_chain},
     };
     use crate::components::Activation;
-    use crate::components::activation::ActivationTag;
 
     #[test]
     fn parsed_valid_amino_acid_strand() {
-        let sequence = [
-            AminoAcid::D,
-            AminoAcid::A,
-            AminoAcid::P,
-            AminoAcid::W,
+        let mut sequence = amino_acid_header!(Accumulator<Norepinephrine>).to_vec();
+        sequence.extend_from_slice(&[
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-        ];
+            AminoAcid::STOP,
+        ]);
 
         let mut app = App::new();
 
@@ -108,31 +174,32 @@ mod test {
 
     #[test]
     fn parsed_valid_activation() {
-        let header = amino_acid_header!(ActivationTag);
-        let sequence = [
-            header[0],
-            header[1],
-            header[2],
-            header[3],
+        let mut sequence = amino_acid_header!(Activation).to_vec();
+        sequence.extend_from_slice(&[
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-        ];
+            AminoAcid::STOP,
+        ]);
 
         let mut app = App::new();
 
         app.add_systems(Update, parse_amino_acid_strand);
 
-        app.world_mut()
-            .spawn((AminoAcidReader, AminoAcidChain(sequence.to_vec())));
+        let chain = app
+            .world_mut()
+            .spawn((AminoAcidReader, AminoAcidChain(sequence.to_vec())))
+            .id();
 
         app.update();
 
+        // The chain is read once and keeps a report of what was built
+        let report = app.world().get::<ParseReport>(chain).unwrap();
+        assert_eq!(report.genes.len(), 1);
+        assert_eq!(report.failures().count(), 0);
+        assert!(app.world().get::<AminoAcidReader>(chain).is_none());
+
         let world: &mut World = app.world_mut();
 
         let mut query = world.query::<&Activation>();
@@ -142,21 +209,18 @@ mod test {
 
     #[test]
     fn bad_header_amino_acid_strand() {
-        // In order to fail to match, there must be 2 or more incorrect amino acids
-        let sequence = [
-            AminoAcid::A,
-            AminoAcid::R,
-            AminoAcid::P,
-            AminoAcid::W,
+        // In order to fail to match, half of the header or more must be incorrect
+        let mut sequence = amino_acid_header!(Accumulator<Norepinephrine>).to_vec();
+        for acid in &mut sequence[..PROMOTER_SIZE / 2] {
+            *acid = if *acid == AminoAcid::R { AminoAcid::W } else { AminoAcid::R };
+        }
+        sequence.extend_from_slice(&[
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
             AminoAcid::R,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-            AminoAcid::UNKNOWN,
-        ];
+            AminoAcid::STOP,
+        ]);
 
         let mut app = App::new();
 
@@ -173,4 +237,126 @@ mod test {
 
         assert!(query.iter(app.world()).len() == 0);
     }
+
+    // Takes two amino acids and never builds anything
+    fn broken_gene(_: &[AminoAcid], _: EntityCommands) -> Result<usize, GeneParseError> {
+        Err(GeneParseError {
+            consumed: 2,
+            message: "broken".to_string(),
+        })
+    }
+
+    static BROKEN: ComponentRegister<PROMOTER_SIZE> = ComponentRegister {
+        id: GeneId::new("broken", 1),
+        salt: None,
+        type_str: "Broken",
+        parser: broken_gene,
+        encoder: encode_nothing,
+        from_text: no_text,
+        to_text: no_payload,
+    };
+
+    #[test]
+    fn broken_genes_are_reported_and_skipped() {
+        let accumulator = HeaderTable::registered()
+            .iter()
+            .find(|(register, _)| register.id == <Accumulator<Norepinephrine> as Gene>::ID)
+            .unwrap()
+            .0;
+        let table = HeaderTable::assign([&BROKEN, accumulator], HEADER_MIN_DISTANCE).unwrap();
+
+        // A broken gene, followed by a working accumulator
+        let mut sequence = table.header_of(BROKEN.id).unwrap().to_vec();
+        sequence.extend_from_slice(&[AminoAcid::A, AminoAcid::A]);
+        sequence.extend_from_slice(&table.header_of(accumulator.id).unwrap());
+        sequence.extend_from_slice(&[AminoAcid::R, AminoAcid::STOP]);
+
+        let mut world = World::new();
+        let organism = world.spawn_empty().id();
+        let report = read_chain(&sequence, &table, world.commands().entity(organism));
+        world.flush();
+
+        assert_eq!(report.genes.len(), 2);
+        assert_eq!(report.failures().count(), 1);
+
+        let broken = &report.genes[0];
+        assert_eq!(broken.type_str, "Broken");
+        assert_eq!(broken.header, 0..PROMOTER_SIZE);
+        assert_eq!(broken.consumed, PROMOTER_SIZE..PROMOTER_SIZE + 2);
+        assert_eq!(broken.error.as_deref(), Some("broken"));
+
+        let accumulator = &report.genes[1];
+        assert_eq!(accumulator.distance, 0);
+        assert_eq!(accumulator.consumed.end, sequence.len());
+        assert!(accumulator.error.is_none());
+
+        assert_eq!(
+            world
+                .get::<Accumulator<Norepinephrine>>(organism)
+                .unwrap()
+                .buildup_rate,
+            1
+        );
+    }
+
+    fn ignore_gene(_: &[AminoAcid], _: EntityCommands) -> Result<usize, GeneParseError> {
+        Ok(0)
+    }
+
+    fn encode_nothing(_: &EntityRef) -> Option<Result<Vec<AminoAcid>, String>> {
+        None
+    }
+
+    fn no_text(_: &str) -> Result<Vec<AminoAcid>, String> {
+        Err("no text".to_string())
+    }
+
+    fn no_payload(_: &[AminoAcid]) -> String {
+        String::new()
+    }
+
+    // Headers twice the usual length, matched the same way as the registered ones
+    static LONG_HEADERS: [ComponentRegister<8>; 2] = [
+        ComponentRegister {
+            id: GeneId::new("first", 1),
+            salt: None,
+            type_str: "First",
+            parser: ignore_gene,
+            encoder: encode_nothing,
+            from_text: no_text,
+            to_text: no_payload,
+        },
+        ComponentRegister {
+            id: GeneId::new("second", 1),
+            salt: None,
+            type_str: "Second",
+            parser: ignore_gene,
+            encoder: encode_nothing,
+            from_text: no_text,
+            to_text: no_payload,
+        },
+    ];
+
+    #[test]
+    fn longer_headers_tolerate_more_edits() {
+        let table = HeaderTable::assign(&LONG_HEADERS, 7).unwrap();
+        let header = table.header_of(LONG_HEADERS[1].id).unwrap();
+        let mut strand = vec![AminoAcid::STOP; 3];
+        strand.extend_from_slice(&header);
+        for acid in &mut strand[3..6] {
+            *acid = if *acid == AminoAcid::R { AminoAcid::W } else { AminoAcid::R };
+        }
+
+        let found = find_header_among(&strand, &table).unwrap();
+        assert_eq!(found.register.type_str, "Second");
+        assert_eq!(found.index, 3);
+        assert!(found.distance <= 3);
+
+        strand[6] = if strand[6] == AminoAcid::R { AminoAcid::W } else { AminoAcid::R };
+        let distance = generic_levenshtein::distance(&strand[3..], &header);
+        assert_eq!(
+            find_header_among(&strand, &table).is_some(),
+            distance < 4
+        );
+    }
 }
@@ -0,0 +1,316 @@
+use bevy::ecs::{
+    entity::Entity,
+    query::Without,
+    resource::Resource,
+    system::{Commands, Query, Res},
+};
+use gene_traits::{
+    amino_acid::{AminoAcid, GeneticCode},
+    rna,
+};
+
+use crate::components::{
+    amino_acid_reader::{AminoAcidChain, AminoAcidReader},
+    gene_reader::RnaStrand,
+};
+
+// The genetic code strands are translated with.  Swapping it changes how every gene translated
+// afterwards is read, the standard code is used unless something else is inserted.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ActiveGeneticCode(pub GeneticCode);
+
+/**
+ * Reads the strand the way a ribosome would.  Nothing is read until the first start codon, which
+ * may sit in any frame, and the codons after it are looked up in the codon table until a stop
+ * codon comes along.  The stop is kept at the end of the chain so the gene parsers can tell where
+ * the gene ends, everything after it is dropped along with trailing nucleotides that don't make
+ * up a full codon.  A strand without a start codon gives an empty chain.
+ */
+pub fn translate(strand: &[rna::Nucleotide], code: &GeneticCode) -> Vec<AminoAcid> {
+    let Some(start) = strand
+        .windows(3)
+        .position(|codon| code.translate([codon[0], codon[1], codon[2]]) == AminoAcid::START)
+    else {
+        return Vec::new();
+    };
+
+    let mut chain = Vec::new();
+    for codon in strand[start + 3..].chunks_exact(3) {
+        let acid = code.translate([codon[0], codon[1], codon[2]]);
+        chain.push(acid);
+        if acid == AminoAcid::STOP {
+            break;
+        }
+    }
+    chain
+}
+
+// The chain is put on the same entity as the strand, which keeps the link to the organism intact
+// for the ribosome.
+pub fn translate_rna_strand(
+    query: Query<(Entity, &RnaStrand), Without<AminoAcidChain>>,
+    code: Option<Res<ActiveGeneticCode>>,
+    mut commands: Commands,
+) {
+    let code = code.map(|code| code.0).unwrap_or_default();
+    for (entity, strand) in query.iter() {
+        commands
+            .entity(entity)
+            .insert((AminoAcidReader, AminoAcidChain(translate(strand, &code))));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, Update},
+        ecs::{schedule::IntoScheduleConfigs, world::World},
+    };
+    use gene_traits::{
+        amino_acid::{self, AminoAcid, GeneticCode},
+        dna,
+        rna,
+    };
+
+    use super::{ActiveGeneticCode, translate, translate_rna_strand};
+    use crate::{
+        components::{
+            Dopamine,
+            accumulator::Accumulator,
+            amino_acid_reader::AminoAcidChain,
+            gene_reader::{AttachedGenome, GeneParser, Genome, RnaStrand, TATA_BOXES},
+        },
+        systems::ribosome::parse_amino_acid_strand,
+    };
+
+    const NUCLEOTIDES: [dna::Nucleotide; 4] = [
+        dna::Nucleotide::A,
+        dna::Nucleotide::C,
+        dna::Nucleotide::T,
+        dna::Nucleotide::G,
+    ];
+
+    // Picks the first codon for each amino acid that doesn't accidentally spell out a promoter
+    fn encode(genome: &mut Vec<dna::Nucleotide>, acids: &[AminoAcid]) {
+        for &acid in acids {
+            let codon = NUCLEOTIDES
+                .iter()
+                .flat_map(|&a| {
+                    NUCLEOTIDES
+                        .iter()
+                        .flat_map(move |&b| NUCLEOTIDES.map(|c| [a, b, c]))
+                })
+                .filter(|codon| amino_acid::from_rna_triple(codon.map(rna::from_dna)) == acid)
+                .find(|codon| {
+                    let mut candidate = genome.clone();
+                    candidate.extend_from_slice(codon);
+                    candidate[candidate.len().saturating_sub(6)..]
+                        .windows(4)
+                        .all(|w| !TATA_BOXES.iter().any(|b| b == w))
+                })
+                .expect("Every amino acid has a promoter free codon");
+            genome.extend_from_slice(&codon);
+        }
+    }
+
+    #[test]
+    fn translate_groups_codons() {
+        let strand = [
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::C,
+            rna::Nucleotide::A,
+            rna::Nucleotide::G,
+            rna::Nucleotide::G,
+            rna::Nucleotide::G,
+            rna::Nucleotide::U,
+            rna::Nucleotide::U,
+        ];
+
+        assert_eq!(
+            translate(&strand, &GeneticCode::STANDARD),
+            vec![AminoAcid::R, AminoAcid::V]
+        );
+        // The wobble code starts at AU codons instead, the codons after it group the same way
+        let mut wobble = strand;
+        wobble[1] = rna::Nucleotide::U;
+        assert_eq!(
+            translate(&wobble, &GeneticCode::WOBBLE),
+            vec![AminoAcid::R, AminoAcid::V]
+        );
+    }
+
+    // The start codon and any codon but a stop can follow each other without spelling out a
+    // promoter.  With ATT as the start every codon beginning with A would have formed an ATTA box.
+    #[test]
+    fn start_codon_cannot_complete_a_promoter() {
+        let start = [dna::Nucleotide::A; 3];
+        assert_eq!(
+            amino_acid::from_rna_triple(start.map(rna::from_dna)),
+            AminoAcid::START
+        );
+        for a in NUCLEOTIDES {
+            for b in NUCLEOTIDES {
+                for c in NUCLEOTIDES {
+                    let codon = [a, b, c];
+                    if amino_acid::from_rna_triple(codon.map(rna::from_dna)) == AminoAcid::STOP {
+                        continue;
+                    }
+                    let bases = [start, codon].concat();
+                    assert!(
+                        bases.windows(4).all(|w| !TATA_BOXES.iter().any(|b| b == w)),
+                        "{:?}",
+                        codon
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn translate_honours_start_and_stop() {
+        // Start in the second frame, then R, a stop and a codon that must not be read
+        let strand = [
+            rna::Nucleotide::G,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::C,
+            rna::Nucleotide::A,
+            rna::Nucleotide::U,
+            rna::Nucleotide::U,
+            rna::Nucleotide::U,
+            rna::Nucleotide::G,
+            rna::Nucleotide::G,
+            rna::Nucleotide::G,
+        ];
+        assert_eq!(
+            translate(&strand, &GeneticCode::STANDARD),
+            vec![AminoAcid::R, AminoAcid::STOP]
+        );
+
+        // Without a start codon nothing is read
+        assert!(translate(&strand[..3], &GeneticCode::STANDARD).is_empty());
+        assert!(translate(&strand[10..], &GeneticCode::STANDARD).is_empty());
+    }
+
+    #[test]
+    fn integration_translates_rna_strand() {
+        let mut app = App::new();
+        app.add_systems(Update, translate_rna_strand);
+
+        app.world_mut().spawn(RnaStrand(vec![
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::A,
+            rna::Nucleotide::C,
+            rna::Nucleotide::A,
+        ]));
+
+        app.update();
+
+        let world: &mut World = app.world_mut();
+        let mut query = world.query::<&AminoAcidChain>();
+        let chains: Vec<&AminoAcidChain> = query.iter(app.world()).collect();
+
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].0, vec![AminoAcid::R]);
+    }
+
+    #[test]
+    fn integration_uses_the_active_genetic_code() {
+        let mut code = GeneticCode::STANDARD;
+        code.set(
+            [rna::Nucleotide::A, rna::Nucleotide::C, rna::Nucleotide::A],
+            AminoAcid::W,
+        );
+
+        let mut app = App::new();
+        app.insert_resource(ActiveGeneticCode(code));
+        app.add_systems(Update, translate_rna_strand);
+
+        let strand = app
+            .world_mut()
+            .spawn(RnaStrand(vec![
+                rna::Nucleotide::A,
+                rna::Nucleotide::A,
+                rna::Nucleotide::A,
+                rna::Nucleotide::A,
+                rna::Nucleotide::C,
+                rna::Nucleotide::A,
+            ]))
+            .id();
+
+        app.update();
+
+        let chain = app.world().get::<AminoAcidChain>(strand).unwrap();
+        assert_eq!(chain.0, vec![AminoAcid::W]);
+    }
+
+    #[test]
+    fn integration_genome_becomes_accumulator_on_organism() {
+        let header = crate::amino_acid_header!(Accumulator<Dopamine>);
+
+        // Promoter, a spacer so the last A of the promoter doesn't start translation a base early,
+        // then the gene: start, header, a buildup rate of 4 and the stop
+        let mut genome = vec![
+            dna::Nucleotide::A,
+            dna::Nucleotide::T,
+            dna::Nucleotide::T,
+            dna::Nucleotide::A,
+            dna::Nucleotide::G,
+        ];
+        encode(&mut genome, &[AminoAcid::START]);
+        encode(&mut genome, &header);
+        encode(&mut genome, &[AminoAcid::R; 4]);
+        encode(&mut genome, &[AminoAcid::STOP]);
+        genome.push(dna::Nucleotide::G);
+        genome.extend_from_slice(&TATA_BOXES[0]);
+
+        let mut app = App::new();
+        app.add_systems(
+            Update,
+            (
+                crate::components::gene_reader::parse_attached_genome,
+                translate_rna_strand,
+                parse_amino_acid_strand,
+            )
+                .chain(),
+        );
+
+        let genome_entity = app
+            .world_mut()
+            .spawn(Genome {
+                sequence: genome.into(),
+            })
+            .id();
+        let organism = app
+            .world_mut()
+            .spawn((
+                GeneParser {},
+                AttachedGenome {
+                    genome_entity,
+                    start_index: 0,
+                },
+            ))
+            .id();
+
+        app.update();
+
+        let accumulator = app
+            .world()
+            .get::<Accumulator<Dopamine>>(organism)
+            .expect("The organism should have grown a dopamine accumulator");
+        assert_eq!(accumulator.buildup_rate, 4);
+
+        // Running again must not transcribe the genome a second time
+        app.update();
+        let world: &mut World = app.world_mut();
+        let mut query = world.query::<&AminoAcidChain>();
+        assert_eq!(query.iter(app.world()).len(), 1);
+    }
+}